@@ -1,8 +1,95 @@
-use crate::{util, util::FileDialogReader};
+use crate::{
+    util,
+    util::{FileDialogReader, FileKind},
+};
 use dot2shader::*;
 use eframe::{egui, epi};
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 
+/// texture of the loaded pixel art
+#[derive(Clone)]
+struct PreviewTexture(egui::TextureHandle);
+
+impl std::fmt::Debug for PreviewTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PreviewTexture").field(&self.0.id()).finish()
+    }
+}
+
+/// Palettes larger than this are not shown in the palette editor.
+const PALETTE_EDITOR_LIMIT: usize = 256;
+/// At most this number of edits can be undone.
+const UNDO_LIMIT: usize = 20;
+/// Geekest code longer than this is warned as over the character budget of twigl.
+const TWIGL_LENGTH_LIMIT: usize = 4096;
+/// Palette colors within this RGB distance are reported as nearly identical after loading.
+const SIMILAR_COLOR_DISTANCE: f32 = 8.0;
+
+/// limits of the loaded image, adjustable in the side panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LoadLimits {
+    /// Files of this size in KB or more are rejected.
+    file_size_kb: usize,
+    /// Images with more colors than this are rejected.
+    palette_size: usize,
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        Self {
+            file_size_kb: 15,
+            palette_size: PALETTE_SIZE_LIMIT,
+        }
+    }
+}
+
+/// undo and redo stacks of the palette edits
+#[derive(Clone, Debug, Default)]
+struct EditHistory {
+    undo: VecDeque<PixelArt>,
+    redo: Vec<PixelArt>,
+}
+
+impl EditHistory {
+    /// Pushes a state to undo, dropping the oldest one over `UNDO_LIMIT`.
+    fn push_undo(&mut self, state: PixelArt) {
+        if self.undo.len() >= UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(state);
+    }
+    /// Records the state before an edit.
+    fn record(&mut self, state: PixelArt) {
+        self.push_undo(state);
+        self.redo.clear();
+    }
+    /// Returns the state before the last edit, keeping `current` for redo.
+    fn undo(&mut self, current: PixelArt) -> Option<PixelArt> {
+        let state = self.undo.pop_back()?;
+        self.redo.push(current);
+        Some(state)
+    }
+    /// Returns the state before the last undo, keeping `current` for undo.
+    fn redo(&mut self, current: PixelArt) -> Option<PixelArt> {
+        let state = self.redo.pop()?;
+        self.push_undo(current);
+        Some(state)
+    }
+    fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+fn to_color_image(pixel_art: &PixelArt) -> egui::ColorImage {
+    let [width, height] = pixel_art.dimensions();
+    egui::ColorImage::from_rgba_unmultiplied(
+        [width as usize, height as usize],
+        &pixel_art.to_rgba8(),
+    )
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Dot2ShaderApp {
     pixel_art: Arc<Mutex<Option<PixelArt>>>,
@@ -10,7 +97,16 @@ pub struct Dot2ShaderApp {
     message: Arc<Mutex<String>>,
     config: DisplayConfig,
     file_reader: Option<FileDialogReader>,
+    config_reader: Option<FileDialogReader>,
     previous_config: DisplayConfig,
+    preview_image: Arc<Mutex<Option<egui::ColorImage>>>,
+    preview: Option<PreviewTexture>,
+    selected_swatch: Option<usize>,
+    editing_swatch: Option<usize>,
+    recolor_recorded: bool,
+    history: Arc<Mutex<EditHistory>>,
+    file_name: Arc<Mutex<String>>,
+    limits: LoadLimits,
 }
 
 /// panel setting
@@ -22,10 +118,63 @@ impl Dot2ShaderApp {
         use InlineLevel::*;
         let inline_level = &mut self.config.inline_level;
         ui.label("Inline Level");
-        let mut set_radio_value = move |val, msg| ui.radio_value(inline_level, val, msg);
+        let mut set_radio_value = |val, msg| ui.radio_value(inline_level, val, msg);
         set_radio_value(None, "no magic number, for Shadertoy");
         set_radio_value(InlineVariable, "inline constant variables, for Shadertoy");
-        set_radio_value(Geekest, "crazy optimization, for twigl geekest");
+        set_radio_value(Geekest, "crazy optimization, for twigl");
+        let geekest = self.is_geekest_mode();
+        ui.add_enabled_ui(geekest, |ui| {
+            let twigl_mode = &mut self.config.twigl_mode;
+            ui.horizontal(|ui| {
+                ui.label("twigl mode:");
+                ui.radio_value(twigl_mode, TwiglMode::Geekest, "geekest/geeker (300 es)");
+                ui.radio_value(twigl_mode, TwiglMode::Geek, "geek (300 es)");
+                ui.radio_value(twigl_mode, TwiglMode::Classic, "classic (300 es)");
+            });
+        });
+        let template = &mut self.config.template;
+        if geekest {
+            *template = Template::Shadertoy;
+        }
+        let mut standalone = *template == Template::Standalone;
+        let check_standalone = egui::Checkbox::new(
+            &mut standalone,
+            "Self-contained void main(), for GLSL sandboxes.",
+        );
+        if ui.add_enabled(!geekest, check_standalone).changed() {
+            *template = match standalone {
+                true => Template::Standalone,
+                false => Template::Shadertoy,
+            };
+        }
+        let check_minify = egui::Checkbox::new(
+            &mut self.config.minify,
+            "Minify the code, keeping the names.",
+        );
+        ui.add_enabled(!geekest, check_minify);
+        let format_style = &mut self.config.format_style;
+        let mut compact = *format_style == FormatStyle::Compact;
+        let check_compact = egui::Checkbox::new(
+            &mut compact,
+            "Write each array in one line without indentation.",
+        );
+        if ui.add_enabled(!geekest, check_compact).changed() {
+            *format_style = match compact {
+                true => FormatStyle::Compact,
+                false => FormatStyle::Pretty,
+            };
+        }
+        let inline_none = self.config.inline_level == InlineLevel::None;
+        let check_comment = egui::Checkbox::new(
+            &mut self.config.header_comment,
+            "Explain the code by a comment.",
+        );
+        ui.add_enabled(inline_none, check_comment);
+        let check_palette_len = egui::Checkbox::new(
+            &mut self.config.palette_len_constant,
+            "Emit the palette size as PALETTE_LEN.",
+        );
+        ui.add_enabled(inline_none, check_palette_len);
     }
     fn pallet_color_format_setting(&mut self, ui: &mut egui::Ui) {
         use PaletteFormat::*;
@@ -45,13 +194,62 @@ impl Dot2ShaderApp {
         add_palette_radio(IntegerHexadecimal, "single hexadecimal integer");
         add_palette_radio(RGBDecimal, "vec3, specified by decimal integers");
         add_palette_radio(RGBHexadecimal, "vec3, specified by hexadecimal integers");
+        add_palette_radio(Grayscale, "float luminance, lossy for colors");
+        add_palette_radio(RGBADecimal, "vec4, specified by decimal integers");
+        add_palette_radio(RGBAFloat, "vec4, specified by floats");
+        add_palette_radio(PackedARGB, "single 0xAARRGGBB uint, unpacked to vec4");
         ui.radio_value(palette_format, RGBFloat, "vec3, specified by floats");
+        let palette_as_defines = &mut self.config.palette_as_defines;
+        if geekest {
+            *palette_as_defines = false;
+            self.config.inline_palette = false;
+            self.config.vec4_get_color = false;
+        }
+        let check_defines = egui::Checkbox::new(
+            palette_as_defines,
+            "Emit the palette as #define macros, for tiny palettes.",
+        );
+        ui.add_enabled(!geekest, check_defines);
+        let check_inline = egui::Checkbox::new(
+            &mut self.config.inline_palette,
+            "Write the colors in getColor, for no more than 4 colors.",
+        );
+        ui.add_enabled(!geekest, check_inline);
+        let check_vec4 = egui::Checkbox::new(
+            &mut self.config.vec4_get_color,
+            "Return vec4 from getColor.",
+        );
+        ui.add_enabled(!geekest, check_vec4);
+        let hexadecimal = matches!(
+            self.config.palette_format,
+            IntegerHexadecimal | RGBHexadecimal | PackedARGB
+        );
+        let check_uppercase = egui::Checkbox::new(
+            &mut self.config.uppercase_hex,
+            "Uppercase hexadecimal digits.",
+        );
+        ui.add_enabled(hexadecimal, check_uppercase);
+        let rgb_integer = matches!(
+            self.config.palette_format,
+            RGBDecimal | RGBHexadecimal | RGBADecimal
+        );
+        let normalization = &mut self.config.normalization;
+        ui.add_enabled_ui(rgb_integer, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("scale to float by:");
+                ui.radio_value(normalization, Normalization::Divide, "/ 255.0");
+                ui.radio_value(normalization, Normalization::Multiply, "* (1.0 / 255.0)");
+            });
+        });
     }
     fn buffer_format_setting(&mut self, ui: &mut egui::Ui) {
         let geekest = self.is_geekest_mode();
         let buffer_format = &mut self.config.buffer_format;
         if geekest {
             buffer_format.force_to_raw = false;
+            buffer_format.encoding = BufferEncoding::Packed;
+            buffer_format.column_major = false;
+            buffer_format.two_dimensional = false;
         }
         ui.label("Buffer Optimization");
         ui.checkbox(
@@ -67,6 +265,273 @@ impl Dot2ShaderApp {
             "Force not to compress the buffer.",
         );
         ui.add_enabled(!geekest, check_force_to_raw);
+        let check_column_major = egui::Checkbox::new(
+            &mut buffer_format.column_major,
+            "Store the pixels column by column.",
+        );
+        ui.add_enabled(!geekest, check_column_major);
+        let raw = buffer_format.force_to_raw;
+        let check_two_dimensional = egui::Checkbox::new(
+            &mut buffer_format.two_dimensional,
+            "Display the raw buffer as a 2D array. (GLSL ES 3.10)",
+        );
+        ui.add_enabled(!geekest && raw, check_two_dimensional);
+        ui.checkbox(
+            &mut buffer_format.hex_literals,
+            "Write the buffer in hexadecimal.",
+        );
+        ui.add_enabled_ui(!geekest, |ui| {
+            ui.horizontal(|ui| {
+                let encoding = &mut buffer_format.encoding;
+                ui.radio_value(encoding, BufferEncoding::Packed, "packed");
+                ui.radio_value(encoding, BufferEncoding::RunLength, "run-length");
+                ui.radio_value(encoding, BufferEncoding::Smallest, "smaller one");
+            });
+        });
+    }
+    /// Applies `edit` to the pixel art, then updates the preview and the code.
+    /// The previous state is recorded for undo if `record` is true.
+    fn edit_pixel_art(
+        &mut self,
+        record: bool,
+        edit: impl FnOnce(&mut PixelArt) -> Result<(), Error>,
+    ) {
+        let mut pixel_art = self.pixel_art.lock().unwrap();
+        if let Some(pixel_art) = pixel_art.as_mut() {
+            let previous = pixel_art.clone();
+            match edit(pixel_art) {
+                Ok(()) => {
+                    if record {
+                        self.history.lock().unwrap().record(previous);
+                    }
+                    *self.preview_image.lock().unwrap() = Some(to_color_image(pixel_art));
+                }
+                Err(e) => *self.message.lock().unwrap() = e.to_string(),
+            }
+        }
+        drop(pixel_art);
+        util::spawn(self.string_update_closure());
+    }
+    /// Replaces the pixel art with the state taken from the history by `step`.
+    fn step_history(&mut self, step: impl FnOnce(&mut EditHistory, PixelArt) -> Option<PixelArt>) {
+        let mut pixel_art = self.pixel_art.lock().unwrap();
+        let state = pixel_art
+            .clone()
+            .and_then(|current| step(&mut self.history.lock().unwrap(), current));
+        if let Some(state) = state {
+            *self.preview_image.lock().unwrap() = Some(to_color_image(&state));
+            *pixel_art = Some(state);
+            drop(pixel_art);
+            self.selected_swatch = None;
+            self.editing_swatch = None;
+            util::spawn(self.string_update_closure());
+        }
+    }
+    fn undo(&mut self) {
+        self.step_history(EditHistory::undo);
+    }
+    fn redo(&mut self) {
+        self.step_history(EditHistory::redo);
+    }
+    /// Ctrl+Z for undo, Ctrl+Shift+Z or Ctrl+Y for redo
+    fn undo_shortcut_handle(&mut self, ctx: &egui::CtxRef) {
+        let input = ctx.input();
+        let command = input.modifiers.command;
+        let shift = input.modifiers.shift;
+        let undo = command && !shift && input.key_pressed(egui::Key::Z);
+        let redo = command
+            && (shift && input.key_pressed(egui::Key::Z) || input.key_pressed(egui::Key::Y));
+        drop(input);
+        if undo {
+            self.undo();
+        } else if redo {
+            self.redo();
+        }
+    }
+    fn undo_buttons(&mut self, ui: &mut egui::Ui) {
+        let history = self.history.lock().unwrap();
+        let (can_undo, can_redo) = (!history.undo.is_empty(), !history.redo.is_empty());
+        drop(history);
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(can_undo, egui::Button::new("Undo"))
+                .clicked()
+            {
+                self.undo();
+            }
+            if ui
+                .add_enabled(can_redo, egui::Button::new("Redo"))
+                .clicked()
+            {
+                self.redo();
+            }
+        });
+    }
+    fn background_setting(&mut self, ui: &mut egui::Ui) {
+        use BackgroundMode::*;
+        let geekest = self.is_geekest_mode();
+        let background = &mut self.config.background;
+        if geekest {
+            *background = Gray;
+        }
+        ui.label("Background");
+        ui.add_enabled_ui(!geekest, |ui| {
+            ui.radio_value(background, Gray, "gray");
+            ui.horizontal(|ui| {
+                let selected = matches!(background, Color(_));
+                if ui.radio(selected, "solid color").clicked() && !selected {
+                    *background = Color(0x000000);
+                }
+                if let Color(color) = background {
+                    let [_, r, g, b] = color.to_be_bytes();
+                    let mut rgb = [r, g, b];
+                    if egui::color_picker::color_edit_button_srgb(ui, &mut rgb).changed() {
+                        *color = u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]);
+                    }
+                }
+            });
+            ui.radio_value(background, Discard, "transparent");
+            ui.radio_value(background, DiscardFragment, "discard");
+            ui.radio_value(background, ClampToEdge, "extend the edges");
+            ui.radio_value(background, Repeat, "tile the pixel art");
+        });
+        let bounds_check = &mut self.config.bounds_check;
+        if geekest {
+            *bounds_check = BoundsCheck::Unchecked;
+        }
+        ui.add_enabled_ui(!geekest, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("getColor out of range:");
+                ui.radio_value(bounds_check, BoundsCheck::Unchecked, "unchecked");
+                ui.radio_value(bounds_check, BoundsCheck::Clamp, "clamp");
+                ui.radio_value(bounds_check, BoundsCheck::Background, "background");
+            });
+        });
+    }
+    fn scale_setting(&mut self, ui: &mut egui::Ui) {
+        use DisplayScale::*;
+        let geekest = self.is_geekest_mode();
+        let scale = &mut self.config.scale;
+        if geekest {
+            *scale = FitHeight;
+        }
+        ui.label("Scale");
+        ui.add_enabled_ui(!geekest, |ui| {
+            ui.radio_value(scale, FitHeight, "fit to the screen height");
+            ui.horizontal(|ui| {
+                let selected = matches!(scale, Pixels(_));
+                if ui.radio(selected, "screen pixels per pixel").clicked() && !selected {
+                    *scale = Pixels(8);
+                }
+                if let Pixels(n) = scale {
+                    ui.add(egui::DragValue::new(n).clamp_range(1..=256));
+                }
+            });
+            ui.horizontal(|ui| {
+                let selected = matches!(scale, Percent(_));
+                if ui.radio(selected, "% of the screen height").clicked() && !selected {
+                    *scale = Percent(50);
+                }
+                if let Percent(percent) = scale {
+                    let drag = egui::DragValue::new(percent)
+                        .clamp_range(1..=1000)
+                        .suffix("%");
+                    ui.add(drag);
+                }
+            });
+        });
+        let rounding = &mut self.config.rounding;
+        if geekest {
+            *rounding = Rounding::Floor;
+        }
+        ui.add_enabled_ui(!geekest, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("rounding:");
+                ui.radio_value(rounding, Rounding::Floor, "floor");
+                ui.radio_value(rounding, Rounding::Round, "round");
+            });
+        });
+    }
+    fn palette_setting(&mut self, ui: &mut egui::Ui) {
+        let palette = match self.pixel_art.lock().unwrap().as_ref() {
+            Some(pixel_art) => pixel_art.palette().clone(),
+            None => return,
+        };
+        ui.label("Palette");
+        self.undo_buttons(ui);
+        if palette.len() > PALETTE_EDITOR_LIMIT {
+            ui.label(format!(
+                "Palette editing supports no more than {PALETTE_EDITOR_LIMIT} colors."
+            ));
+            return;
+        }
+        ui.label("Click two colors to swap, double-click a color to change it.");
+        let mut clicked = None;
+        ui.horizontal_wrapped(|ui| {
+            palette.iter().enumerate().for_each(|(i, color)| {
+                let [_, r, g, b] = color.to_be_bytes();
+                let (rect, response) =
+                    ui.allocate_exact_size(egui::vec2(20.0, 20.0), egui::Sense::click());
+                ui.painter()
+                    .rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+                if self.selected_swatch == Some(i) || self.editing_swatch == Some(i) {
+                    let stroke_color = ui.visuals().selection.stroke.color;
+                    ui.painter().rect_stroke(rect, 2.0, (2.0, stroke_color));
+                }
+                if response.double_clicked() {
+                    self.selected_swatch = None;
+                    self.editing_swatch = Some(i);
+                    self.recolor_recorded = false;
+                } else if response.clicked() {
+                    clicked = Some(i);
+                }
+            });
+        });
+        if let Some(i) = clicked {
+            match self.selected_swatch.take() {
+                Some(j) if j != i => {
+                    self.edit_pixel_art(true, |pixel_art| pixel_art.swap_palette_index(i, j))
+                }
+                Some(_) => {}
+                None => self.selected_swatch = Some(i),
+            }
+        }
+        let editing = self
+            .editing_swatch
+            .and_then(|i| palette.get(i).map(|color| (i, *color)));
+        if let Some((i, color)) = editing {
+            let [_, r, g, b] = color.to_be_bytes();
+            let mut rgb = [r, g, b];
+            ui.horizontal(|ui| {
+                ui.label(format!("color {i}"));
+                if ui.color_edit_button_srgb(&mut rgb).changed() {
+                    let color = u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]);
+                    // a drag of the color picker is undone at once
+                    let record = !self.recolor_recorded;
+                    self.recolor_recorded = true;
+                    self.edit_pixel_art(record, |pixel_art| pixel_art.set_palette_color(i, color));
+                }
+                if ui.button("Done").clicked() {
+                    self.editing_swatch = None;
+                }
+            });
+        }
+    }
+    fn limits_setting(&mut self, ui: &mut egui::Ui) {
+        let limits = &mut self.limits;
+        ui.horizontal(|ui| {
+            ui.label("file size limit:");
+            let file_size = egui::DragValue::new(&mut limits.file_size_kb)
+                .clamp_range(1..=1 << 20)
+                .suffix("KB");
+            ui.add(file_size);
+        });
+        ui.horizontal(|ui| {
+            ui.label("palette size limit:");
+            let palette_size =
+                egui::DragValue::new(&mut limits.palette_size).clamp_range(1..=1 << 24);
+            ui.add(palette_size);
+        });
     }
     fn setting_change_string_update(&mut self) {
         if self.previous_config != self.config {
@@ -77,12 +542,12 @@ impl Dot2ShaderApp {
     }
     fn file_open_button(&mut self, ui: &mut egui::Ui) {
         if ui.button("File Open...").clicked() {
-            self.file_reader = FileDialogReader::start();
+            self.file_reader = FileDialogReader::start(FileKind::Image);
         }
         match self.file_reader.as_ref().map(FileDialogReader::result) {
-            Some(Some(Ok(result))) => {
+            Some(Some(Ok((name, result)))) => {
                 let closure = self.pixel_art_update_closure();
-                util::spawn(move || closure(result));
+                util::spawn(move || closure(name, result));
             }
             Some(Some(Err(error))) => {
                 *self.message.lock().unwrap() = error;
@@ -90,11 +555,63 @@ impl Dot2ShaderApp {
             _ => {}
         }
     }
+    fn dropped_file_handle(&mut self, ctx: &egui::CtxRef) {
+        let result = ctx
+            .input()
+            .raw
+            .dropped_files
+            .first()
+            .map(util::read_dropped_file);
+        match result {
+            Some(Ok((name, result))) => {
+                let closure = self.pixel_art_update_closure();
+                util::spawn(move || closure(name, result));
+            }
+            Some(Err(error)) => {
+                *self.message.lock().unwrap() = error;
+            }
+            None => {}
+        }
+    }
     fn copy_button(&self, ui: &mut egui::Ui) {
         if ui.button("Copy Code").clicked() {
             ui.output().copied_text = self.string.lock().unwrap().clone();
         }
     }
+    fn download_button(&self, ui: &mut egui::Ui) {
+        if ui.button("Download .glsl").clicked() {
+            let file_name = util::code_file_name(&self.file_name.lock().unwrap());
+            let string = self.string.lock().unwrap().clone();
+            if let Err(e) = util::save_text_file(&file_name, FileKind::Code, &string) {
+                *self.message.lock().unwrap() = e;
+            }
+        }
+    }
+    fn export_config_button(&self, ui: &mut egui::Ui) {
+        if ui.button("Export Config").clicked() {
+            let result = serde_json::to_string_pretty(&self.config)
+                .map_err(|e| e.to_string())
+                .and_then(|json| util::save_text_file("dot2shader.json", FileKind::Config, &json));
+            if let Err(e) = result {
+                *self.message.lock().unwrap() = e;
+            }
+        }
+    }
+    fn import_config_button(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Import Config...").clicked() {
+            self.config_reader = FileDialogReader::start(FileKind::Config);
+        }
+        let result = match self.config_reader.as_ref().map(FileDialogReader::result) {
+            Some(Some(result)) => result,
+            _ => return,
+        };
+        match result.and_then(|(_, json)| {
+            serde_json::from_slice::<DisplayConfig>(&json).map_err(|e| e.to_string())
+        }) {
+            Ok(config) => self.config = config,
+            Err(error) => *self.message.lock().unwrap() = error,
+        }
+    }
     fn error_message_label(&mut self, ui: &mut egui::Ui) {
         let message = self.message.lock().unwrap().clone();
         ui.add(egui::Label::new(
@@ -129,6 +646,16 @@ impl Dot2ShaderApp {
             self.pallet_color_format_setting(ui);
             ui.separator();
             self.buffer_format_setting(ui);
+            ui.separator();
+            self.background_setting(ui);
+            ui.separator();
+            self.scale_setting(ui);
+            ui.separator();
+            self.palette_setting(ui);
+            ui.horizontal(|ui| {
+                self.export_config_button(ui);
+                self.import_config_button(ui);
+            });
             self.setting_change_string_update();
         }
         ui.separator();
@@ -136,6 +663,7 @@ impl Dot2ShaderApp {
         if loaded {
             ui.horizontal(|ui| {
                 self.copy_button(ui);
+                self.download_button(ui);
                 ui.with_layout(egui::Layout::right_to_left(), |ui| {
                     self.file_open_button(ui);
                 })
@@ -143,10 +671,22 @@ impl Dot2ShaderApp {
         } else {
             self.file_open_button(ui);
         }
+        ui.collapsing("Limits", |ui| self.limits_setting(ui));
         self.error_message_label(ui);
         egui::warn_if_debug_build(ui);
         self.bottom_credit(ui);
     }
+    fn preview_panel(&mut self, ctx: &egui::CtxRef, ui: &mut egui::Ui) {
+        if let Some(image) = self.preview_image.lock().unwrap().take() {
+            self.preview = Some(PreviewTexture(ctx.load_texture("preview", image)));
+        }
+        if let Some(PreviewTexture(texture)) = &self.preview {
+            let size = texture.size_vec2();
+            let scale = f32::min(256.0 / size.y, ui.available_width() / size.x);
+            ui.image(texture, size * scale);
+            ui.separator();
+        }
+    }
     fn string_update_closure(&self) -> impl Fn() -> Option<()> + 'static {
         let pixel_art = Arc::clone(&self.pixel_art);
         let string = Arc::clone(&self.string);
@@ -158,19 +698,31 @@ impl Dot2ShaderApp {
                 .display(config)
                 .map_err(|e| *message.lock().unwrap() = e.to_string())
                 .ok()?;
+            let len = display.estimated_len();
+            if config.inline_level == InlineLevel::Geekest && len > TWIGL_LENGTH_LIMIT {
+                *message.lock().unwrap() =
+                    format!("The code exceeds {TWIGL_LENGTH_LIMIT} chars for twigl. length: {len}");
+            }
             let new_string = display.to_string();
             *string.lock().unwrap() = new_string;
             Some(())
         }
     }
-    fn pixel_art_update_closure(&self) -> impl Fn(Vec<u8>) -> Option<()> + 'static {
+    fn pixel_art_update_closure(&self) -> impl Fn(String, Vec<u8>) -> Option<()> + 'static {
         let message = Arc::clone(&self.message);
+        let file_name = Arc::clone(&self.file_name);
         let pixel_art = Arc::clone(&self.pixel_art);
+        let preview_image = Arc::clone(&self.preview_image);
+        let history = Arc::clone(&self.history);
         let string_update_closure = self.string_update_closure();
-        move |buffer| {
-            if buffer.len() >= 1024 * 15 {
+        let LoadLimits {
+            file_size_kb,
+            palette_size,
+        } = self.limits;
+        move |name, buffer| {
+            if buffer.len() >= 1024 * file_size_kb {
                 *message.lock().unwrap() = format!(
-                    "File size must be less than 15KB. file size: {}KB",
+                    "File size must be less than {file_size_kb}KB. file size: {}KB",
                     buffer.len() / 1024
                 );
                 return None;
@@ -179,18 +731,27 @@ impl Dot2ShaderApp {
                 .map_err(|e| *message.lock().unwrap() = e.to_string())
                 .ok()
                 .filter(|pixel_art| {
-                    let palette_size_limit = pixel_art.palette().len() <= usize::pow(2, 16);
+                    let palette_size_limit = pixel_art.palette().len() <= palette_size;
                     if !palette_size_limit {
                         *message.lock().unwrap() = format!(
-                            "Palette size is must be no more than {}. Palette size: {}",
-                            usize::pow(2, 16),
+                            "Palette size is must be no more than {palette_size}. Palette size: {}",
                             pixel_art.palette().len()
                         );
                     }
                     palette_size_limit
                 })?;
-            *message.lock().unwrap() = String::new();
+            let analysis = new_pixel_art.analyze(SIMILAR_COLOR_DISTANCE);
+            *message.lock().unwrap() = match analysis.similar_pairs {
+                0 => String::new(),
+                pairs => format!(
+                    "{pairs} pairs of the {} palette colors are nearly identical. The image may be noisy; consider merging similar colors.",
+                    analysis.colors
+                ),
+            };
+            *preview_image.lock().unwrap() = Some(to_color_image(&new_pixel_art));
             *pixel_art.lock().unwrap() = Some(new_pixel_art);
+            history.lock().unwrap().clear();
+            *file_name.lock().unwrap() = name;
             string_update_closure()
         }
     }
@@ -214,12 +775,15 @@ impl epi::App for Dot2ShaderApp {
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &epi::Frame) {
         ctx.set_pixels_per_point(4.0 / 3.0);
+        self.dropped_file_handle(ctx);
+        self.undo_shortcut_handle(ctx);
         egui::SidePanel::left("side_panel")
             .default_width(290.0)
             .resizable(false)
             .show(ctx, |ui| self.side_panel_rayout(ui));
 
         egui::CentralPanel::default().show(ctx, |ui| {
+            self.preview_panel(ctx, ui);
             let mut string = self.string.lock().unwrap().clone();
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.add_sized(
@@ -11,9 +11,121 @@ pub fn spawn<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(closure: F) {
     });
 }
 
+/// Gets the name and the content of the file dropped into the window.
+/// The native backend gives the path and the web backend gives the bytes.
+pub fn read_dropped_file(file: &eframe::egui::DroppedFile) -> Result<(String, Vec<u8>), String> {
+    match (&file.bytes, &file.path) {
+        (Some(bytes), _) => Ok((file.name.clone(), bytes.to_vec())),
+        (None, Some(path)) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let buffer = std::fs::read(path).map_err(|e| e.to_string())?;
+            Ok((name.into_owned(), buffer))
+        }
+        (None, None) => Err(format!("cannot read the dropped file: {}", file.name)),
+    }
+}
+
+/// kind of the files read or written by the dialogs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    /// pixel art image, PNG, BMP, or GIF
+    Image,
+    /// generated GLSL code
+    Code,
+    /// `DisplayConfig` as JSON
+    Config,
+}
+
+impl FileKind {
+    /// name of the filter in the native dialog
+    #[cfg(not(target_arch = "wasm32"))]
+    fn filter_name(self) -> &'static str {
+        match self {
+            FileKind::Image => "pixel dot file",
+            FileKind::Code => "GLSL file",
+            FileKind::Config => "config json file",
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            FileKind::Image => &["png", "bmp", "gif"],
+            FileKind::Code => &["glsl"],
+            FileKind::Config => &["json"],
+        }
+    }
+    /// `accept` attribute of the file input
+    #[cfg(target_arch = "wasm32")]
+    fn accept(self) -> &'static str {
+        match self {
+            FileKind::Image => "image/png, image/gif, image/bmp",
+            FileKind::Code => ".glsl",
+            FileKind::Config => "application/json, .json",
+        }
+    }
+    /// `id` of the file input, one for each kind
+    #[cfg(target_arch = "wasm32")]
+    fn input_id(self) -> &'static str {
+        match self {
+            FileKind::Image => "file-input",
+            FileKind::Code => "code-file-input",
+            FileKind::Config => "config-file-input",
+        }
+    }
+}
+
+/// Name of the generated code file, e.g. `heart.glsl` for `heart.png`.
+pub fn code_file_name(image_file_name: &str) -> String {
+    let stem = std::path::Path::new(image_file_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or_else(|| "dot2shader".into());
+    format!("{stem}.glsl")
+}
+
+/// Saves `text` as a file via the save file dialog.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_text_file(file_name: &str, kind: FileKind, text: &str) -> Result<(), String> {
+    let path = native_dialog::FileDialog::new()
+        .set_filename(file_name)
+        .add_filter(kind.filter_name(), kind.extensions())
+        .show_save_single_file()
+        .map_err(|e| e.to_string())?;
+    match path {
+        Some(path) => std::fs::write(path, text).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Saves `text` as a file by the browser download.
+#[cfg(target_arch = "wasm32")]
+pub fn save_text_file(file_name: &str, _kind: FileKind, text: &str) -> Result<(), String> {
+    (|| {
+        let doc = web_sys::window()
+            .and_then(|win| win.document())
+            .ok_or_else(|| JsValue::from_str("no document"))?;
+        let parts = js_sys::Array::of1(&JsValue::from_str(text));
+        let blob = web_sys::Blob::new_with_str_sequence_and_options(
+            &parts,
+            web_sys::BlobPropertyBag::new().type_("text/plain"),
+        )?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+        let anchor = doc.create_element("a")?;
+        let anchor = web_sys::HtmlAnchorElement::from(JsValue::from(anchor));
+        anchor.set_href(&url);
+        anchor.set_download(file_name);
+        anchor.click();
+        web_sys::Url::revoke_object_url(&url)
+    })()
+    .map_err(|e: JsValue| format!("cannot download the file. JsValue: {:?}", e))
+}
+
 #[derive(Clone, Debug)]
 pub struct FileDialogReader {
-    result: Arc<Mutex<Option<Vec<u8>>>>,
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    kind: FileKind,
+    result: Arc<Mutex<Option<(String, Vec<u8>)>>>,
     error: Arc<Mutex<Option<String>>>,
 }
 
@@ -23,24 +135,29 @@ impl FileDialogReader {
         *error.lock().unwrap() = Some(e.to_string());
     }
     /// Starts file reading
-    pub fn start() -> Option<Self> {
+    pub fn start(kind: FileKind) -> Option<Self> {
         let result = Arc::new(Mutex::new(None));
         let error = Arc::new(Mutex::new(None));
         let path = native_dialog::FileDialog::new()
-            .add_filter("pixel dot file", &["png", "bmp", "gif"])
+            .add_filter(kind.filter_name(), kind.extensions())
             .show_open_single_file()
             .map_err(|e| Self::register_error(&e, &error))
             .ok()?;
         if let Some(path) = path {
-            let buffer = std::fs::read(path)
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let buffer = std::fs::read(&path)
                 .map_err(|e| Self::register_error(&e, &error))
                 .ok();
-            *result.lock().unwrap() = buffer;
+            *result.lock().unwrap() = buffer.map(|buffer| (name.into_owned(), buffer));
         }
-        Some(Self { result, error })
+        Some(Self {
+            kind,
+            result,
+            error,
+        })
     }
-    /// Gets result of file reading. Returns `None` if the file has not been read yet.
-    pub fn result(&self) -> Option<Result<Vec<u8>, String>> {
+    /// Gets result of file reading, the file name and the content. Returns `None` if the file has not been read yet.
+    pub fn result(&self) -> Option<Result<(String, Vec<u8>), String>> {
         if let Some(result) = self.result.lock().unwrap().take() {
             Some(Ok(result))
         } else {
@@ -49,23 +166,24 @@ impl FileDialogReader {
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-const FILE_INPUT_NAME: &str = "file-input";
 #[cfg(target_arch = "wasm32")]
 use eframe::wasm_bindgen::{prelude::*, JsCast};
 
 #[cfg(target_arch = "wasm32")]
 impl FileDialogReader {
-    fn get_input(error: &Arc<Mutex<Option<String>>>) -> Option<web_sys::HtmlInputElement> {
+    fn get_input(
+        kind: FileKind,
+        error: &Arc<Mutex<Option<String>>>,
+    ) -> Option<web_sys::HtmlInputElement> {
         let doc = web_sys::window().and_then(|win| win.document())?;
         let body = doc.body()?;
-        let input = doc.get_element_by_id(FILE_INPUT_NAME).or_else(|| {
+        let input = doc.get_element_by_id(kind.input_id()).or_else(|| {
             (|| {
                 let file_input = doc.create_element("input")?;
-                file_input.set_id(FILE_INPUT_NAME);
+                file_input.set_id(kind.input_id());
                 file_input.set_attribute("type", "file")?;
                 file_input.set_attribute("style", "display:none")?;
-                file_input.set_attribute("accept", "image/png, image/gif, image/bmp")?;
+                file_input.set_attribute("accept", kind.accept())?;
                 body.append_child(&file_input)?;
                 Ok(file_input)
             })()
@@ -77,11 +195,15 @@ impl FileDialogReader {
         })?;
         Some(web_sys::HtmlInputElement::from(JsValue::from(input)))
     }
-    pub fn start() -> Option<Self> {
+    pub fn start(kind: FileKind) -> Option<Self> {
         let result = Arc::new(Mutex::new(None));
         let error = Arc::new(Mutex::new(None));
-        Self::get_input(&error)?.click();
-        Some(Self { result, error })
+        Self::get_input(kind, &error)?.click();
+        Some(Self {
+            kind,
+            result,
+            error,
+        })
     }
     fn start_file_read(&self, file: &web_sys::File) -> Option<()> {
         let error = Arc::clone(&self.error);
@@ -100,6 +222,7 @@ impl FileDialogReader {
             .ok()?;
         let clone_reader = reader.clone();
         let clone_result = Arc::clone(&self.result);
+        let name = file.name();
         let closure = Closure::wrap(Box::new(move || {
             let buffer = clone_reader
                 .result()
@@ -109,19 +232,19 @@ impl FileDialogReader {
                         Some(format!("something wrong for read result. JsValue: {:?}", e));
                     e
                 })?;
-            *clone_result.lock().unwrap() = Some(buffer);
+            *clone_result.lock().unwrap() = Some((name.clone(), buffer));
             Ok(())
         }) as Box<dyn FnMut() -> Result<(), JsValue>>);
         reader.set_onload(Some(closure.into_js_value().unchecked_ref()));
         Some(())
     }
-    pub fn result(&self) -> Option<Result<Vec<u8>, String>> {
+    pub fn result(&self) -> Option<Result<(String, Vec<u8>), String>> {
         if let Some(result) = self.result.lock().unwrap().take() {
             return Some(Ok(result));
         } else if let Some(error) = self.error.lock().unwrap().take() {
             return Some(Err(error));
         }
-        let input = Self::get_input(&self.error)?;
+        let input = Self::get_input(self.kind, &self.error)?;
         if let Some(file) = input.files().and_then(|files| files.get(0)) {
             self.start_file_read(&file);
         }
@@ -0,0 +1,10 @@
+pub const SIZE: [u32; 2] = [3, 2];
+pub const CHUNKS_IN_U32: u32 = 16;
+pub const PALETTE: [u32; 3] = [
+    0xff0000,
+    0xff00,
+    0xff,
+];
+pub const BUFFER: [u32; 1] = [
+    2337,
+];
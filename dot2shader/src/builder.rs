@@ -0,0 +1,236 @@
+use crate::*;
+
+/// builder of `DisplayConfig`, e.g.
+/// `DisplayConfig::builder().inline_level(InlineLevel::Geekest).palette_format(PaletteFormat::RGBFloat).build()`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisplayConfigBuilder {
+    config: DisplayConfig,
+}
+
+impl DisplayConfigBuilder {
+    /// Returns the config, or the error of `DisplayConfig::validate`.
+    pub fn build(self) -> Result<DisplayConfig, Error> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+    #[inline]
+    pub fn buffer_format(mut self, buffer_format: BufferFormat) -> Self {
+        self.config.buffer_format = buffer_format;
+        self
+    }
+    #[inline]
+    pub fn palette_format(mut self, palette_format: PaletteFormat) -> Self {
+        self.config.palette_format = palette_format;
+        self
+    }
+    #[inline]
+    pub fn inline_level(mut self, inline_level: InlineLevel) -> Self {
+        self.config.inline_level = inline_level;
+        self
+    }
+    #[inline]
+    pub fn reverse_rows(mut self, reverse_rows: bool) -> Self {
+        self.config.buffer_format.reverse_rows = reverse_rows;
+        self
+    }
+    #[inline]
+    pub fn reverse_each_chunk(mut self, reverse_each_chunk: bool) -> Self {
+        self.config.buffer_format.reverse_each_chunk = reverse_each_chunk;
+        self
+    }
+    #[inline]
+    pub fn force_to_raw(mut self, force_to_raw: bool) -> Self {
+        self.config.buffer_format.force_to_raw = force_to_raw;
+        self
+    }
+    #[inline]
+    pub fn bit_width(mut self, bit_width: Option<u8>) -> Self {
+        self.config.buffer_format.bit_width = bit_width;
+        self
+    }
+    #[inline]
+    pub fn tight_packing(mut self, tight_packing: bool) -> Self {
+        self.config.buffer_format.tight_packing = tight_packing;
+        self
+    }
+    #[inline]
+    pub fn force_uint(mut self, force_uint: bool) -> Self {
+        self.config.buffer_format.force_uint = force_uint;
+        self
+    }
+    #[inline]
+    pub fn pack_uvec2(mut self, pack_uvec2: bool) -> Self {
+        self.config.buffer_format.pack_uvec2 = pack_uvec2;
+        self
+    }
+    #[inline]
+    pub fn pack_uvec4(mut self, pack_uvec4: bool) -> Self {
+        self.config.buffer_format.pack_uvec4 = pack_uvec4;
+        self
+    }
+    #[inline]
+    pub fn encoding(mut self, encoding: BufferEncoding) -> Self {
+        self.config.buffer_format.encoding = encoding;
+        self
+    }
+    #[inline]
+    pub fn column_major(mut self, column_major: bool) -> Self {
+        self.config.buffer_format.column_major = column_major;
+        self
+    }
+    #[inline]
+    pub fn two_dimensional(mut self, two_dimensional: bool) -> Self {
+        self.config.buffer_format.two_dimensional = two_dimensional;
+        self
+    }
+    #[inline]
+    pub fn hex_literals(mut self, hex_literals: bool) -> Self {
+        self.config.buffer_format.hex_literals = hex_literals;
+        self
+    }
+    #[inline]
+    pub fn target(mut self, target: Target) -> Self {
+        self.config.target = target;
+        self
+    }
+    #[inline]
+    pub fn glsl_version(mut self, glsl_version: Option<GlslVersion>) -> Self {
+        self.config.glsl_version = glsl_version;
+        self
+    }
+    #[inline]
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.config.precision = precision;
+        self
+    }
+    #[inline]
+    pub fn scale(mut self, scale: DisplayScale) -> Self {
+        self.config.scale = scale;
+        self
+    }
+    #[inline]
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.config.rounding = rounding;
+        self
+    }
+    #[inline]
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.config.normalization = normalization;
+        self
+    }
+    #[inline]
+    pub fn index_base(mut self, index_base: IndexBase) -> Self {
+        self.config.index_base = index_base;
+        self
+    }
+    #[inline]
+    pub fn vec4_get_color(mut self, vec4_get_color: bool) -> Self {
+        self.config.vec4_get_color = vec4_get_color;
+        self
+    }
+    #[inline]
+    pub fn format_style(mut self, format_style: FormatStyle) -> Self {
+        self.config.format_style = format_style;
+        self
+    }
+    #[inline]
+    pub fn palette_len_constant(mut self, palette_len_constant: bool) -> Self {
+        self.config.palette_len_constant = palette_len_constant;
+        self
+    }
+    #[inline]
+    pub fn bounds_check(mut self, bounds_check: BoundsCheck) -> Self {
+        self.config.bounds_check = bounds_check;
+        self
+    }
+    #[inline]
+    pub fn premultiplied_alpha(mut self, premultiplied_alpha: bool) -> Self {
+        self.config.premultiplied_alpha = premultiplied_alpha;
+        self
+    }
+    #[inline]
+    pub fn palette_as_defines(mut self, palette_as_defines: bool) -> Self {
+        self.config.palette_as_defines = palette_as_defines;
+        self
+    }
+    #[inline]
+    pub fn template(mut self, template: Template) -> Self {
+        self.config.template = template;
+        self
+    }
+    #[inline]
+    pub fn twigl_mode(mut self, twigl_mode: TwiglMode) -> Self {
+        self.config.twigl_mode = twigl_mode;
+        self
+    }
+    #[inline]
+    pub fn palette_limit(mut self, palette_limit: Option<usize>) -> Self {
+        self.config.palette_limit = palette_limit;
+        self
+    }
+    #[inline]
+    pub fn background(mut self, background: BackgroundMode) -> Self {
+        self.config.background = background;
+        self
+    }
+    #[inline]
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.config.minify = minify;
+        self
+    }
+    #[inline]
+    pub fn header_comment(mut self, header_comment: bool) -> Self {
+        self.config.header_comment = header_comment;
+        self
+    }
+    #[inline]
+    pub fn indent(mut self, indent: Indent) -> Self {
+        self.config.indent = indent;
+        self
+    }
+    #[inline]
+    pub fn elements_per_line(mut self, elements_per_line: Option<usize>) -> Self {
+        self.config.elements_per_line = elements_per_line;
+        self
+    }
+    #[inline]
+    pub fn uppercase_hex(mut self, uppercase_hex: bool) -> Self {
+        self.config.uppercase_hex = uppercase_hex;
+        self
+    }
+    #[inline]
+    pub fn inline_palette(mut self, inline_palette: bool) -> Self {
+        self.config.inline_palette = inline_palette;
+        self
+    }
+}
+
+#[test]
+fn builder() {
+    let config = DisplayConfig::builder()
+        .palette_format(PaletteFormat::IntegerHexadecimal)
+        .reverse_rows(false)
+        .build()
+        .unwrap();
+    let expected = DisplayConfig {
+        palette_format: PaletteFormat::IntegerHexadecimal,
+        buffer_format: BufferFormat {
+            reverse_rows: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(config, expected);
+    assert!(matches!(
+        DisplayConfig::builder()
+            .inline_level(InlineLevel::Geekest)
+            .build(),
+        Err(Error::NotMeetGeekest)
+    ));
+    let geekest = DisplayConfig::builder()
+        .inline_level(InlineLevel::Geekest)
+        .palette_format(PaletteFormat::RGBFloat)
+        .build()
+        .unwrap();
+    assert_eq!(geekest.inline_level, InlineLevel::Geekest);
+}
@@ -5,23 +5,331 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Formatter;
+use std::str::FromStr;
+
+mod builder;
+mod minify;
+mod multipass;
+mod quantize;
+mod target;
+
+pub use builder::DisplayConfigBuilder;
+pub use multipass::MultipassCode;
+pub use quantize::{ColorSpace, PaletteAnalysis};
+
+/// Splits RGBA8 pixels into the palette, in the order of first appearance, and the indices.
+/// The alpha is kept in the palette as `0xTTRRGGBB`, where `TT` is `255 - alpha`, if `palette_alpha`.
+fn index_colors_sequential(rgba: &[u8], palette_alpha: bool) -> (Vec<u32>, Vec<u32>) {
+    let mut col2idx = HashMap::new();
+    let buffer: Vec<_> = rgba
+        .chunks(4)
+        .map(|e| {
+            let idx = col2idx.len();
+            let transparency = match palette_alpha {
+                true => 0xff - e[3],
+                false => 0,
+            };
+            *col2idx
+                .entry(u32::from_be_bytes([transparency, e[0], e[1], e[2]]))
+                .or_insert(idx as u32)
+        })
+        .collect();
+    let mut palette = vec![0; col2idx.len()];
+    col2idx
+        .into_iter()
+        .for_each(|(idx, i)| palette[i as usize] = idx);
+    (palette, buffer)
+}
+
+#[cfg(not(feature = "parallel"))]
+#[inline]
+fn index_colors(rgba: &[u8], palette_alpha: bool) -> (Vec<u32>, Vec<u32>) {
+    index_colors_sequential(rgba, palette_alpha)
+}
+
+/// Indexes each block of pixels in parallel, then merges the palettes of the blocks in order,
+/// so the palette is the same as `index_colors_sequential`.
+#[cfg(feature = "parallel")]
+fn index_colors(rgba: &[u8], palette_alpha: bool) -> (Vec<u32>, Vec<u32>) {
+    use rayon::prelude::*;
+    const BLOCK_PIXELS: usize = 1 << 16;
+    let blocks: Vec<(Vec<u32>, Vec<u32>)> = rgba
+        .par_chunks(BLOCK_PIXELS * 4)
+        .map(|block| index_colors_sequential(block, palette_alpha))
+        .collect();
+    let mut col2idx = HashMap::new();
+    let mut palette = Vec::new();
+    let local2global: Vec<Vec<u32>> = blocks
+        .iter()
+        .map(|(local_palette, _)| {
+            local_palette
+                .iter()
+                .map(|color| {
+                    *col2idx.entry(*color).or_insert_with(|| {
+                        palette.push(*color);
+                        palette.len() as u32 - 1
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    let mut buffer = vec![0; rgba.len() / 4];
+    buffer
+        .par_chunks_mut(BLOCK_PIXELS)
+        .zip(blocks.par_iter().zip(local2global.par_iter()))
+        .for_each(|(buffer, ((_, local_buffer), local2global))| {
+            buffer
+                .iter_mut()
+                .zip(local_buffer)
+                .for_each(|(idx, local)| *idx = local2global[*local as usize]);
+        });
+    (palette, buffer)
+}
+
+/// Maps the pixels whose alpha is less than `alpha_threshold` to one palette entry `0x000000`,
+/// keeping the order of first appearance.
+/// Returns the new palette, the new buffer and the index of the transparent entry if any.
+fn index_transparent(
+    rgba: &[u8],
+    palette: &[u32],
+    buffer: &[u32],
+    alpha_threshold: u8,
+) -> (Vec<u32>, Vec<u32>, Option<u32>) {
+    let mut old2new = HashMap::new();
+    let mut new_palette = Vec::new();
+    let buffer = buffer
+        .iter()
+        .zip(rgba.chunks(4))
+        .map(|(idx, e)| {
+            let key = (e[3] >= alpha_threshold).then_some(*idx);
+            *old2new.entry(key).or_insert_with(|| {
+                new_palette.push(key.map_or(0, |idx| palette[idx as usize]));
+                new_palette.len() as u32 - 1
+            })
+        })
+        .collect();
+    (new_palette, buffer, old2new.get(&None).copied())
+}
+
+#[test]
+fn index_colors_order() {
+    let rgba: Vec<u8> = (0..(1 << 18) + 3)
+        .flat_map(|i: u32| {
+            let [_, r, g, b] = (i * 7919 % 1000).to_be_bytes();
+            [r, g, b, 0xff]
+        })
+        .collect();
+    let (palette, buffer) = index_colors(&rgba, false);
+    assert_eq!(
+        (palette.clone(), buffer.clone()),
+        index_colors_sequential(&rgba, false)
+    );
+    assert_eq!(palette.len(), 1000);
+    assert_eq!(palette[..3], [0, 919, 838]);
+    assert!(buffer.iter().enumerate().all(|(i, idx)| {
+        let e = &rgba[i * 4..i * 4 + 4];
+        palette[*idx as usize] == u32::from_be_bytes([0, e[0], e[1], e[2]])
+    }));
+}
+
+/// Rounds 16 bit channels to the nearest 8 bit values, e.g. `0x00ff` to `1`,
+/// where `image` would drop the lower byte.
+fn rgba16_to_rgba8(rgba: &[u16]) -> Vec<u8> {
+    rgba.iter()
+        .map(|c| ((*c as u32 * 255 + 32767) / 65535) as u8)
+        .collect()
+}
+
+#[test]
+fn downsample_16_bit() {
+    let rgba = [0, 0x00ff, 0x0100, 0x8080, 0xfeff, 0xffff];
+    assert_eq!(rgba16_to_rgba8(&rgba), vec![0, 1, 1, 128, 254, 255]);
+}
+
+/// Unpacks `width` indices of `bit_depth` bits from a row starting from a new byte,
+/// where the leftmost pixel is in the high-order bits as in PNG and BMP.
+fn unpack_row(row: &[u8], width: usize, bit_depth: usize) -> impl Iterator<Item = u32> + '_ {
+    let mask = ((1u32 << bit_depth) - 1) as u8;
+    (0..width).map(move |x| {
+        let (byte, offset) = (x * bit_depth / 8, x * bit_depth % 8);
+        (row[byte] >> (8 - bit_depth - offset) & mask) as u32
+    })
+}
+
+/// the composited frames of a GIF
+fn gif_frames(image_buffer: &[u8]) -> Result<Vec<image::RgbaImage>, Error> {
+    use image::AnimationDecoder;
+    let decoder = image::codecs::gif::GifDecoder::new(image_buffer)?;
+    let frames = decoder.into_frames().collect_frames()?;
+    Ok(frames.into_iter().map(image::Frame::into_buffer).collect())
+}
 
 /// pixel art handler
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PixelArt {
     palette: Vec<u32>,
     buffer: Vec<u32>,
     size: [u32; 2],
+    /// palette index of the transparent pixels, which are drawn as the background
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transparent: Option<u32>,
+    /// alpha of each pixel kept apart from the palette by `ImageOptions::alpha_bits`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alpha: Option<AlphaBuffer>,
+}
+
+/// alpha of each pixel quantized to `2^bits` levels, in the same order as the buffer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlphaBuffer {
+    bits: u8,
+    levels: Vec<u32>,
+}
+
+impl AlphaBuffer {
+    /// Quantizes the alpha of RGBA8 pixels to `bits` bits, rounding to the nearest level.
+    fn from_rgba8(rgba: &[u8], bits: u8) -> AlphaBuffer {
+        let max = (1 << bits) - 1;
+        let levels = rgba
+            .chunks(4)
+            .map(|e| (e[3] as u32 * max + 127) / 255)
+            .collect();
+        AlphaBuffer { bits, levels }
+    }
+    /// bits per pixel, one of 1, 2, 4, and 8
+    #[inline]
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+    /// level of each pixel from `0`, transparent, to `2^bits - 1`, opaque
+    #[inline]
+    pub fn levels(&self) -> &Vec<u32> {
+        &self.levels
+    }
+    /// level converted back to the alpha of 8 bits
+    #[inline]
+    fn alpha8(&self, level: u32) -> u8 {
+        let max = (1 << self.bits) - 1;
+        ((level * 255 + max / 2) / max) as u8
+    }
+}
+
+/// options of `PixelArt::from_image_with`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageOptions {
+    /// Map all the transparent pixels, whose alpha is less than `alpha_threshold`, to one palette entry
+    /// regardless of their RGB, which is drawn as the background. If `false`, alpha is ignored except by `alpha_bits`. default: `false`
+    pub transparency: bool,
+    /// Pixels with alpha less than this are transparent, and the others are opaque with their RGB as is,
+    /// which gives a 1-bit mask for antialiased sprites, e.g. `128`. `1` means only alpha `0` is transparent.
+    /// Used only with `transparency`. Since alpha is binarized, the alpha of the opaque pixels is not kept.
+    /// default: `1`
+    pub alpha_threshold: u8,
+    /// Keep the alpha of each pixel apart from the palette, quantized to `2^alpha_bits` levels,
+    /// so that gradient alpha does not multiply the colors of the palette. One of 1, 2, 4, and 8.
+    /// The palette of an indexed PNG or BMP is then rebuilt as the other images.
+    /// `InlineLevel::Geekest`, `Target::Rust` and `Target::C` ignore the alpha buffer. default: `None`
+    #[serde(default)]
+    pub alpha_bits: Option<u8>,
+    /// Keep the alpha of each color in the palette, so that the same RGB with different alpha are different entries,
+    /// e.g. for `PaletteFormat::RGBAFloat`. The entries are `0xTTRRGGBB`, where `TT` is `255 - alpha`,
+    /// so the entries without alpha are opaque. default: `false`
+    #[serde(default)]
+    pub palette_alpha: bool,
+}
+
+impl ImageOptions {
+    fn validate(&self) -> Result<(), Error> {
+        match self.alpha_bits {
+            Some(bits) if !matches!(bits, 1 | 2 | 4 | 8) => Err(Error::InvalidAlphaBits(bits)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            transparency: false,
+            alpha_threshold: 1,
+            alpha_bits: None,
+            palette_alpha: false,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("{0}")]
     ImageError(image::ImageError),
-    #[error("Supported image format is PNG, BMP, and GIF.")]
+    #[error("{0}")]
+    PngEncodingError(png::EncodingError),
+    #[error("{0}")]
+    PngDecodingError(png::DecodingError),
+    #[error("{0}")]
+    JsonError(serde_json::Error),
+    #[error("Buffer length must be width * height = {width} * {height}. buffer length: {len}")]
+    BufferLengthMismatch { len: usize, width: u32, height: u32 },
+    #[cfg_attr(
+        not(feature = "extra-formats"),
+        error("Supported image format is PNG, BMP, and GIF.")
+    )]
+    #[cfg_attr(
+        feature = "extra-formats",
+        error("Supported image format is PNG, BMP, GIF, JPEG, WebP, and TGA.")
+    )]
     UnsupportedImageFormat,
     #[error("Other configs do not meet the requirement of Geekest.")]
     NotMeetGeekest,
+    #[error("Image has no pixels.")]
+    EmptyImage,
+    #[error("Frame index must be less than the number of the frames {frames}. frame: {frame}")]
+    FrameOutOfRange { frame: usize, frames: usize },
+    #[error("Palette index must be less than the palette size {len}. index: {index}")]
+    PaletteIndexOutOfRange { index: usize, len: usize },
+    #[error("Unknown palette format: {0}. Palette format is one of integer-decimal, integer-hexadecimal, rgb-decimal, rgb-hexadecimal, rgb-float, grayscale, rgba-decimal, rgba-float, and packed-argb.")]
+    UnknownPaletteFormat(String),
+    #[error(
+        "Unknown inline level: {0}. Inline level is one of none, inline-variable, and geekest."
+    )]
+    UnknownInlineLevel(String),
+    #[error(
+        "Unknown GLSL version: {0}. GLSL version is one of 300 es, 310 es, 320 es, 330, and 430."
+    )]
+    UnknownGlslVersion(String),
+    #[error("Bit width must be one of 1, 2, 4, 8, and 16, or up to 16 with tight packing. bit width: {0}")]
+    InvalidBitWidth(u8),
+    #[error("Palette size must be no more than 2^{bit_width}. Palette size: {palette_len}")]
+    PaletteNotFitBitWidth { bit_width: u8, palette_len: usize },
+    #[error("Palette range {start}..{end} must be within the palette size {len}.")]
+    InvalidPaletteRange {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    #[error("Palette size must be no more than {limit}. Palette size: {palette_len}")]
+    PaletteLengthOver { limit: usize, palette_len: usize },
+    #[error("Alpha bits must be one of 1, 2, 4, and 8. alpha bits: {0}")]
+    InvalidAlphaBits(u8),
+    #[error("Alpha level must be less than 2^{bits}. level: {level}")]
+    AlphaLevelOutOfRange { level: u32, bits: u8 },
+    #[error("Display scale must be positive.")]
+    InvalidDisplayScale,
+    #[error("Region {region_width}x{region_height} at ({x}, {y}) is out of the image of {width}x{height}.")]
+    RegionOutOfRange {
+        x: u32,
+        y: u32,
+        region_width: u32,
+        region_height: u32,
+        width: u32,
+        height: u32,
+    },
+    #[error("Pixel ({x}, {y}) is out of the image of {width}x{height}.")]
+    PixelOutOfRange {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
 }
 
 impl From<image::ImageError> for Error {
@@ -30,6 +338,24 @@ impl From<image::ImageError> for Error {
     }
 }
 
+impl From<png::EncodingError> for Error {
+    fn from(e: png::EncodingError) -> Error {
+        Error::PngEncodingError(e)
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(e: png::DecodingError) -> Error {
+        Error::PngDecodingError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::JsonError(e)
+    }
+}
+
 /// palette display format
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PaletteFormat {
@@ -43,19 +369,63 @@ pub enum PaletteFormat {
     RGBHexadecimal,
     /// RGB Float format, e.g. `0.690,0.949,0.388`
     RGBFloat,
+    /// single float luminance, e.g. `0.852`. Colors are converted lossily.
+    Grayscale,
+    /// RGBA Integer format with the alpha in the palette, e.g. `176,242,99,255`
+    RGBADecimal,
+    /// RGBA Float format with the alpha in the palette, e.g. `0.690,0.949,0.388,1`
+    RGBAFloat,
+    /// U32 hexadecimal `0xAARRGGBB` with the alpha in the palette, e.g. `0xffb0f263U`, unpacked to `vec4` by `unpackARGB`
+    PackedARGB,
 }
 
 impl PaletteFormat {
+    /// all palette formats
+    pub const ALL: [PaletteFormat; 9] = [
+        PaletteFormat::IntegerDecimal,
+        PaletteFormat::IntegerHexadecimal,
+        PaletteFormat::RGBDecimal,
+        PaletteFormat::RGBHexadecimal,
+        PaletteFormat::RGBFloat,
+        PaletteFormat::Grayscale,
+        PaletteFormat::RGBADecimal,
+        PaletteFormat::RGBAFloat,
+        PaletteFormat::PackedARGB,
+    ];
+    /// kebab-case name, e.g. `rgb-float`
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteFormat::IntegerDecimal => "integer-decimal",
+            PaletteFormat::IntegerHexadecimal => "integer-hexadecimal",
+            PaletteFormat::RGBDecimal => "rgb-decimal",
+            PaletteFormat::RGBHexadecimal => "rgb-hexadecimal",
+            PaletteFormat::RGBFloat => "rgb-float",
+            PaletteFormat::Grayscale => "grayscale",
+            PaletteFormat::RGBADecimal => "rgba-decimal",
+            PaletteFormat::RGBAFloat => "rgba-float",
+            PaletteFormat::PackedARGB => "packed-argb",
+        }
+    }
     #[inline]
     pub fn is_integer(&self) -> bool {
         use PaletteFormat::*;
         matches!(self, IntegerDecimal | IntegerHexadecimal)
     }
+    /// Whether the palette elements have the alpha.
+    #[inline]
+    pub fn has_alpha(&self) -> bool {
+        use PaletteFormat::*;
+        matches!(self, RGBADecimal | RGBAFloat | PackedARGB)
+    }
     #[inline]
     pub fn element_type(&self) -> &'static str {
-        match self.is_integer() {
-            true => "int",
-            false => "vec3",
+        match self {
+            _ if self.is_integer() => "int",
+            PaletteFormat::PackedARGB => "uint",
+            _ if self.has_alpha() => "vec4",
+            PaletteFormat::Grayscale => "float",
+            _ => "vec3",
         }
     }
 }
@@ -66,8 +436,38 @@ impl Default for PaletteFormat {
     }
 }
 
+impl std::fmt::Display for PaletteFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PaletteFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| Error::UnknownPaletteFormat(s.to_string()))
+    }
+}
+
+/// how `PaletteFormat::RGBDecimal`, `PaletteFormat::RGBHexadecimal` and `PaletteFormat::RGBADecimal`
+/// scale `0..=255` to `0.0..=1.0`.
+/// For the colors divided beforehand, use `PaletteFormat::RGBFloat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Normalization {
+    /// `vec3(176, 242, 99) / 255.0`
+    #[default]
+    Divide,
+    /// `vec3(176, 242, 99) * (1.0 / 255.0)`, for the compilers which do not fold the division
+    Multiply,
+}
+
 /// buffer display format
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BufferFormat {
     /// Turn the picture upside down so that the index starts at the bottom left of the picture. default: `true`
     pub reverse_rows: bool,
@@ -75,6 +475,47 @@ pub struct BufferFormat {
     pub reverse_each_chunk: bool,
     /// Even if the data can be compressed, the buffer will be displayed as an array without compression. default: `false`
     pub force_to_raw: bool,
+    /// Bit width of each index in a compressed chunk, one of 1, 2, 4, 8, and 16.
+    /// Any width from 1 to 16 is allowed with `tight_packing`.
+    /// If `None`, the smallest width that fits the palette is used. default: `None`
+    pub bit_width: Option<u8>,
+    /// Pack indices into a contiguous bit stream, so that an index may straddle two `u32`s.
+    /// The bit width is no longer rounded up to a power of two, and `reverse_each_chunk` is ignored. default: `false`
+    pub tight_packing: bool,
+    /// Display the buffer as `uint` even if every element fits in `int`. default: `false`
+    pub force_uint: bool,
+    /// Pack two compressed `u32`s into a `uvec2`, halving the length of the buffer array.
+    /// Ignored if the buffer is not compressed. default: `false`
+    pub pack_uvec2: bool,
+    /// Pack four compressed `u32`s into a `uvec4`, quartering the length of the buffer array.
+    /// Takes precedence over `pack_uvec2`. Ignored if the buffer is not compressed. default: `false`
+    pub pack_uvec4: bool,
+    /// encoding of the compressed buffer. default: `BufferEncoding::Packed`
+    pub encoding: BufferEncoding,
+    /// Store the pixels column by column from the left, i.e. `idx = u.x * HEIGHT + u.y`.
+    /// `reverse_rows` then decides whether each column starts from the bottom. default: `false`
+    pub column_major: bool,
+    /// Display the uncompressed buffer as a 2D array, i.e. `BUFFER[HEIGHT][WIDTH]` indexed by `BUFFER[u.y][u.x]`.
+    /// Ignored if the buffer is compressed. Arrays of arrays need GLSL ES 3.10 or GLSL 4.30,
+    /// so WebGL 2 including Shadertoy does not accept them. default: `false`
+    pub two_dimensional: bool,
+    /// Write the elements of the buffer arrays as hexadecimal literals, e.g. `0x1b2c3d4e`,
+    /// which show the packed indices digit by digit, also for `Target::Rust` and `Target::C`.
+    /// The palette is not affected. default: `false`
+    pub hex_literals: bool,
+}
+
+/// encoding of the compressed buffer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BufferEncoding {
+    /// Pack the fixed width indices into each `u32`.
+    #[default]
+    Packed,
+    /// Encode each run of the same index as `count << 16 | index` and decode them in a loop.
+    /// Good for large flat areas. `bit_width`, `tight_packing`, `pack_uvec2` and `pack_uvec4` are ignored.
+    RunLength,
+    /// the one of `Packed` and `RunLength` whose buffer is smaller
+    Smallest,
 }
 
 impl Default for BufferFormat {
@@ -83,10 +524,35 @@ impl Default for BufferFormat {
             reverse_rows: true,
             reverse_each_chunk: true,
             force_to_raw: false,
+            bit_width: None,
+            tight_packing: false,
+            force_uint: false,
+            pack_uvec2: false,
+            pack_uvec4: false,
+            encoding: BufferEncoding::Packed,
+            column_major: false,
+            two_dimensional: false,
+            hex_literals: false,
         }
     }
 }
 
+#[test]
+fn buffer_format_json() {
+    // the fields added later take the default
+    let json = r#"{
+    "reverse_rows": false,
+    "reverse_each_chunk": true,
+    "force_to_raw": false
+}"#;
+    let buffer_format: BufferFormat = serde_json::from_str(json).unwrap();
+    let expected = BufferFormat {
+        reverse_rows: false,
+        ..Default::default()
+    };
+    assert_eq!(buffer_format, expected);
+}
+
 /// Indicates the degree of inline
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InlineLevel {
@@ -99,17 +565,317 @@ pub enum InlineLevel {
     /// This inline level requires:
     /// - pallet format is `PaletteFormat::RGBFloat`
     /// - `BufferFormat::force_to_raw == false`
+    /// - `BufferFormat::tight_packing == false`
+    /// - `BufferFormat::pack_uvec2 == false`
+    /// - `BufferFormat::pack_uvec4 == false`
+    /// - `BufferFormat::encoding == BufferEncoding::Packed`
+    /// - `BufferFormat::column_major == false`
+    /// - `BufferFormat::two_dimensional == false`
+    /// - `DisplayConfig::palette_as_defines == false`
+    /// - `DisplayConfig::inline_palette == false`
+    /// - `DisplayConfig::template == Template::Shadertoy`
+    /// - `DisplayConfig::background == BackgroundMode::Gray`
+    /// - `DisplayConfig::glsl_version == None`
+    /// - `DisplayConfig::scale == DisplayScale::FitHeight`
+    /// - `DisplayConfig::rounding == Rounding::Floor`
+    /// - `DisplayConfig::vec4_get_color == false`
+    /// - `DisplayConfig::bounds_check == BoundsCheck::Unchecked`
+    ///
+    /// The transparent pixels are drawn in the color of their palette entry, and the alpha buffer is ignored.
     Geekest,
 }
 
+impl InlineLevel {
+    /// all inline levels
+    pub const ALL: [InlineLevel; 3] = [
+        InlineLevel::None,
+        InlineLevel::InlineVariable,
+        InlineLevel::Geekest,
+    ];
+    /// kebab-case name, e.g. `inline-variable`
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            InlineLevel::None => "none",
+            InlineLevel::InlineVariable => "inline-variable",
+            InlineLevel::Geekest => "geekest",
+        }
+    }
+}
+
 impl Default for InlineLevel {
     fn default() -> Self {
         Self::None
     }
 }
 
+impl std::fmt::Display for InlineLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for InlineLevel {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|level| level.name() == s)
+            .ok_or_else(|| Error::UnknownInlineLevel(s.to_string()))
+    }
+}
+
+#[test]
+fn parse_names() {
+    PaletteFormat::ALL.iter().for_each(|format| {
+        assert_eq!(
+            format.to_string().parse::<PaletteFormat>().unwrap(),
+            *format
+        )
+    });
+    InlineLevel::ALL
+        .iter()
+        .for_each(|level| assert_eq!(level.to_string().parse::<InlineLevel>().unwrap(), *level));
+    assert_eq!(
+        "rgb-float".parse::<PaletteFormat>().unwrap(),
+        PaletteFormat::RGBFloat
+    );
+    assert!(matches!(
+        "RGBFloat".parse::<PaletteFormat>(),
+        Err(Error::UnknownPaletteFormat(_))
+    ));
+    assert!(matches!(
+        "crazy".parse::<InlineLevel>(),
+        Err(Error::UnknownInlineLevel(_))
+    ));
+}
+
+/// indentation of the generated code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Indent {
+    /// `n` spaces, up to 16
+    Spaces(u8),
+    /// a tab
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Self::Spaces(4)
+    }
+}
+
+impl Indent {
+    /// one level of the indentation
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        const SPACES: &str = "                ";
+        match self {
+            Indent::Spaces(n) => &SPACES[..usize::min(*n as usize, SPACES.len())],
+            Indent::Tab => "\t",
+        }
+    }
+}
+
+/// what is drawn outside the pixel art
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    /// gray, `vec3(0.5)`
+    #[default]
+    Gray,
+    /// solid color `0xRRGGBB`
+    Color(u32),
+    /// transparent, i.e. alpha is `0`. The closest analog of `discard` on Shadertoy.
+    Discard,
+    /// `discard` the fragments, so that whatever is already drawn there is kept,
+    /// e.g. the other layers or the previous frame of a Shadertoy buffer.
+    DiscardFragment,
+    /// the color of the nearest pixel on the edge
+    ClampToEdge,
+    /// tile the pixel art infinitely
+    Repeat,
+}
+
+/// what `getColor` does for `u` out of the pixel art, which `mainImage` never passes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoundsCheck {
+    /// Assume `u` is in range, so reading out of range is undefined.
+    #[default]
+    Unchecked,
+    /// Read the nearest pixel on the edge.
+    Clamp,
+    /// Return the color of `BackgroundMode::Color`, gray `0x808080` for `BackgroundMode::Gray`,
+    /// and black for the others.
+    Background,
+}
+
+/// size of the pixel art on the screen drawn by `mainImage`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisplayScale {
+    /// Fit the height of the pixel art to the screen.
+    #[default]
+    FitHeight,
+    /// Draw each pixel as `n`x`n` pixels of the screen, e.g. for a small sprite.
+    Pixels(u32),
+    /// Draw the pixel art as high as this percentage of the screen height. `100` is the same as `FitHeight`.
+    Percent(u32),
+}
+
+/// rounding of the screen coordinates to the pixel coordinates in `mainImage`.
+/// With `DisplayScale::Pixels` on an even resolution, the edges of the pixels fall between the screen pixels
+/// by `Round` if the size of the pixel art is even, and by `Floor` if it is odd.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    /// `floor(x)`
+    #[default]
+    Floor,
+    /// `round(x)`, i.e. `floor(x + 0.5)`, which shifts the pixel art by half a pixel.
+    Round,
+}
+
+/// mode of twigl targeted by `InlineLevel::Geekest`. All of them are the WebGL 2.0 (300 es) ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TwiglMode {
+    /// geekest (300 es), also valid for geeker (300 es). Emits only the body of `main`.
+    /// twigl provides the resolution `r`, the fragment coordinate `FC` and the output `o`.
+    #[default]
+    Geekest,
+    /// geek (300 es). Emits `void main()`.
+    /// twigl provides the resolution `r` and the output `o`, but not the body of `main`.
+    Geek,
+    /// classic (300 es). Emits a complete shader with `#version 300 es`.
+    /// twigl provides only the uniform `resolution`, which is declared by the shader itself.
+    Classic,
+}
+
+/// program which the generated code is embedded in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Template {
+    /// `mainImage` for the Image tab of Shadertoy, which provides `iResolution`. Pasted as is.
+    #[default]
+    Shadertoy,
+    /// self-contained GLSL ES 3.00 fragment shader with `void main()`, writing to `outColor`.
+    /// The resolution must be passed by `uniform vec2 iResolution`.
+    Standalone,
+}
+
+/// version of the `#version` directive
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GlslVersion {
+    /// GLSL ES 3.00 for WebGL 2, as Shadertoy and twigl
+    #[default]
+    Es300,
+    /// GLSL ES 3.10
+    Es310,
+    /// GLSL ES 3.20
+    Es320,
+    /// GLSL 3.30 core
+    Core330,
+    /// GLSL 4.30 core
+    Core430,
+}
+
+impl GlslVersion {
+    /// all GLSL versions
+    pub const ALL: [GlslVersion; 5] = [
+        GlslVersion::Es300,
+        GlslVersion::Es310,
+        GlslVersion::Es320,
+        GlslVersion::Core330,
+        GlslVersion::Core430,
+    ];
+    /// name in the `#version` directive, e.g. `300 es`
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            GlslVersion::Es300 => "300 es",
+            GlslVersion::Es310 => "310 es",
+            GlslVersion::Es320 => "320 es",
+            GlslVersion::Core330 => "330",
+            GlslVersion::Core430 => "430",
+        }
+    }
+}
+
+impl std::fmt::Display for GlslVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for GlslVersion {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|version| version.name() == s)
+            .ok_or_else(|| Error::UnknownGlslVersion(s.to_string()))
+    }
+}
+
+/// default precision of `float` declared after `#version`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precision {
+    Lowp,
+    Mediump,
+    #[default]
+    Highp,
+}
+
+impl Precision {
+    /// qualifier, e.g. `highp`
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Precision::Lowp => "lowp",
+            Precision::Mediump => "mediump",
+            Precision::Highp => "highp",
+        }
+    }
+}
+
+/// language of the generated code
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    /// GLSL shader according to the other configs
+    #[default]
+    Glsl,
+    /// `pub const` arrays `SIZE`, `PALETTE` and `BUFFER` in Rust, e.g. for `include!`.
+    /// The buffer is packed in the same way as GLSL, but the shader specific configs,
+    /// e.g. `inline_level`, `template` and `minify`, are ignored.
+    Rust,
+    /// `static const uint32_t` arrays `palette` and `buffer` with `#define WIDTH` and `HEIGHT` in C.
+    /// The shader specific configs are ignored as with `Target::Rust`.
+    C,
+}
+
+/// whitespace of the arrays and the functions, independent of the names by `InlineLevel`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FormatStyle {
+    /// Break the arrays into lines and indent them and the function bodies by `DisplayConfig::indent`.
+    #[default]
+    Pretty,
+    /// Write each array in one line without spaces, and do not indent the function bodies.
+    /// `InlineLevel::Geekest` is always compact, with no line breaks at all.
+    Compact,
+}
+
+/// the index of the first palette entry in the buffer of `Target::Rust` and `Target::C`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexBase {
+    /// The buffer refers to `PALETTE[0]` by `0`.
+    #[default]
+    Zero,
+    /// The buffer refers to the first entry by `1`, e.g. for the 1-indexed arrays of Lua.
+    /// `INDEX_BASE` and `TRANSPARENT` are emitted in the same base.
+    One,
+}
+
 /// configuation of display
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DisplayConfig {
     /// buffer format
     pub buffer_format: BufferFormat,
@@ -117,6 +883,148 @@ pub struct DisplayConfig {
     pub palette_format: PaletteFormat,
     /// inline level
     pub inline_level: InlineLevel,
+    /// Emit the palette as `#define C0 ...` macros chosen by a `?:` chain instead of an array.
+    /// Meant for tiny palettes. Not available in `InlineLevel::Geekest`.
+    pub palette_as_defines: bool,
+    /// program which the code is embedded in. Not available in `InlineLevel::Geekest`.
+    pub template: Template,
+    /// mode of twigl, used only in `InlineLevel::Geekest`.
+    pub twigl_mode: TwiglMode,
+    /// hard cap of the palette size, e.g. `Some(16)` for GBA-like palettes.
+    /// `display` fails instead of generating the code if the palette is larger. default: `None`
+    pub palette_limit: Option<usize>,
+    /// what is drawn outside the pixel art and at the transparent pixels.
+    /// The transparent pixels are transparent as `BackgroundMode::Discard` with `ClampToEdge` and `Repeat`.
+    /// Not available in `InlineLevel::Geekest`.
+    pub background: BackgroundMode,
+    /// Strip the redundant whitespaces and pack the arrays into long lines, keeping the names.
+    /// The result still compiles on Shadertoy. Ignored in `InlineLevel::Geekest`.
+    pub minify: bool,
+    /// Emit a comment explaining the size, the palette and the packing of the buffer.
+    /// Only in `InlineLevel::None`.
+    pub header_comment: bool,
+    /// indentation of the arrays and the functions. Ignored in `InlineLevel::Geekest` and `FormatStyle::Compact`.
+    pub indent: Indent,
+    /// number of the elements in each line of the buffer array. If `None`, 8 for the compressed buffer,
+    /// 4 for `uvec2`, 2 for `uvec4` and the width of the image for the raw buffer.
+    /// Ignored in `InlineLevel::Geekest` and `FormatStyle::Compact`.
+    pub elements_per_line: Option<usize>,
+    /// Use uppercase digits for the hexadecimal palette formats and `BufferFormat::hex_literals`, e.g. `0xB0F263`.
+    pub uppercase_hex: bool,
+    /// Write the colors into `getColor` by a `?:` chain instead of the palette array
+    /// if the palette has no more than `INLINE_PALETTE_LIMIT` colors. Not available in `InlineLevel::Geekest`.
+    pub inline_palette: bool,
+    /// language of the generated code. default: `Target::Glsl`
+    pub target: Target,
+    /// Prepend `#version` and the `float` precision, e.g. `#version 300 es\nprecision highp float;`,
+    /// so that the code compiles outside Shadertoy. If `None`, there is no header except
+    /// `Template::Standalone`, which uses GLSL ES 3.00. Not available in `InlineLevel::Geekest`. default: `None`
+    pub glsl_version: Option<GlslVersion>,
+    /// precision in the header by `glsl_version`. default: `Precision::Highp`
+    pub precision: Precision,
+    /// size of the pixel art on the screen. Not available in `InlineLevel::Geekest`. default: `DisplayScale::FitHeight`
+    pub scale: DisplayScale,
+    /// rounding of the screen coordinates to the pixel coordinates. Not available in `InlineLevel::Geekest`.
+    /// default: `Rounding::Floor`
+    pub rounding: Rounding,
+    /// scaling of `PaletteFormat::RGBDecimal` and `PaletteFormat::RGBHexadecimal`. default: `Normalization::Divide`
+    pub normalization: Normalization,
+    /// base of the indices in the buffer of `Target::Rust` and `Target::C`.
+    /// Ignored by `Target::Glsl`, whose arrays are always 0-indexed. default: `IndexBase::Zero`
+    pub index_base: IndexBase,
+    /// `getColor` returns `vec4(rgb, 1.0)` instead of the palette element, e.g. for the code expecting
+    /// four components. The palette array keeps the element type. Not available in `InlineLevel::Geekest`.
+    pub vec4_get_color: bool,
+    /// whitespace of the arrays and the functions. default: `FormatStyle::Pretty`
+    pub format_style: FormatStyle,
+    /// Emit the palette size as `PALETTE_LEN` next to `WIDTH` and `HEIGHT`, e.g. for a loop over the palette.
+    /// Only in `InlineLevel::None`. default: `false`
+    pub palette_len_constant: bool,
+    /// guard of `getColor` for `u` out of range, so that `getColor` can be called outside `mainImage`.
+    /// Not available in `InlineLevel::Geekest`. default: `BoundsCheck::Unchecked`
+    pub bounds_check: BoundsCheck,
+    /// Multiply the RGB of `mainImage` by its alpha, for the compositors expecting premultiplied alpha.
+    /// Only with the alpha by `ImageOptions::alpha_bits` or the palette formats with alpha, and the backgrounds keeping the alpha,
+    /// i.e. not `BackgroundMode::Gray` nor `BackgroundMode::Color`. default: `false`
+    pub premultiplied_alpha: bool,
+}
+
+/// the largest palette inlined by `DisplayConfig::inline_palette`
+pub const INLINE_PALETTE_LIMIT: usize = 4;
+
+/// the largest palette `PixelArt::set_pixel` grows to
+pub const PALETTE_SIZE_LIMIT: usize = 1 << 16;
+
+impl DisplayConfig {
+    /// Starts building a config from the default one.
+    #[inline]
+    pub fn builder() -> DisplayConfigBuilder {
+        DisplayConfigBuilder::default()
+    }
+    /// offset added to the indices in the buffer by `index_base`, only for the data targets
+    #[inline]
+    fn index_offset(&self) -> u32 {
+        match (self.target, self.index_base) {
+            (Target::Glsl, _) | (_, IndexBase::Zero) => 0,
+            (_, IndexBase::One) => 1,
+        }
+    }
+    /// Checks the combination of the options which does not depend on the image,
+    /// e.g. the requirements of `InlineLevel::Geekest` and the bit width.
+    pub fn validate(&self) -> Result<(), Error> {
+        let meets_geekest = self.palette_format == PaletteFormat::RGBFloat
+            && !self.buffer_format.force_to_raw
+            && !self.buffer_format.tight_packing
+            && !self.buffer_format.pack_uvec2
+            && !self.buffer_format.pack_uvec4
+            && self.buffer_format.encoding == BufferEncoding::Packed
+            && !self.buffer_format.column_major
+            && !self.buffer_format.two_dimensional
+            && !self.palette_as_defines
+            && !self.inline_palette
+            && self.template == Template::Shadertoy
+            && self.background == BackgroundMode::Gray
+            && self.glsl_version.is_none()
+            && self.scale == DisplayScale::FitHeight
+            && self.rounding == Rounding::Floor
+            && !self.vec4_get_color
+            && self.bounds_check == BoundsCheck::Unchecked;
+        if self.inline_level == InlineLevel::Geekest && !meets_geekest {
+            return Err(Error::NotMeetGeekest);
+        }
+        if matches!(
+            self.scale,
+            DisplayScale::Pixels(0) | DisplayScale::Percent(0)
+        ) {
+            return Err(Error::InvalidDisplayScale);
+        }
+        if let Some(bit_width) = self.buffer_format.bit_width {
+            let aligned = self.buffer_format.tight_packing || 32 % bit_width == 0;
+            if bit_width == 0 || bit_width > 16 || !aligned {
+                return Err(Error::InvalidBitWidth(bit_width));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn validate() {
+    assert!(DisplayConfig::default().validate().is_ok());
+    let mut config = DisplayConfig {
+        inline_level: InlineLevel::Geekest,
+        palette_format: PaletteFormat::IntegerDecimal,
+        ..Default::default()
+    };
+    assert!(matches!(config.validate(), Err(Error::NotMeetGeekest)));
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(config.validate().is_ok());
+    config.buffer_format.force_to_raw = true;
+    assert!(matches!(config.validate(), Err(Error::NotMeetGeekest)));
+    config.inline_level = InlineLevel::None;
+    assert!(config.validate().is_ok());
+    config.buffer_format.bit_width = Some(3);
+    assert!(matches!(config.validate(), Err(Error::InvalidBitWidth(3))));
 }
 
 #[test]
@@ -125,6 +1033,22 @@ fn default_config() {
     std::fs::write("default.json", &string).unwrap();
 }
 
+#[test]
+fn old_config_json() {
+    // `default.json` before the fields added later, which take the default
+    let json = r#"{
+  "buffer_format": {
+    "reverse_rows": true,
+    "reverse_each_chunk": true,
+    "force_to_raw": false
+  },
+  "palette_format": "RGBDecimal",
+  "inline_level": "None"
+}"#;
+    let config: DisplayConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(config, DisplayConfig::default());
+}
+
 /// Pixel art display, format the pixel art according to `DisplayConfig`.
 #[derive(Clone, Copy, Debug)]
 pub struct Display<'a> {
@@ -132,40 +1056,287 @@ pub struct Display<'a> {
     config: DisplayConfig,
 }
 
+/// Statistics of the buffer generated by `Display`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressionStats {
+    /// number of elements of the buffer array
+    pub buffer_len: usize,
+    /// total byte size of the buffer array
+    pub byte_size: usize,
+    /// average bits per pixel
+    pub bits_per_pixel: f32,
+    /// whether the buffer is displayed as `int`, not `uint`
+    pub intable: bool,
+    /// bit width of each index, `None` if the buffer is not compressed
+    pub bit_shift: Option<usize>,
+}
+
+/// Validates `config` and generates the code of `pixel_art` with the statistics of its buffer.
+/// The same as `pixel_art.display(*config)` without holding the `Display`.
+/// Use `Display::write_to` to write the code to a stream without the whole string.
+pub fn encode(
+    pixel_art: &PixelArt,
+    config: &DisplayConfig,
+) -> Result<(String, CompressionStats), Error> {
+    let display = pixel_art.display(*config)?;
+    Ok((display.to_string(), display.stats()))
+}
+
 impl PixelArt {
     /// Creates Bitmap from image file.
+    /// JPEG, WebP, and TGA are also supported with the feature `extra-formats`.
+    /// The images of 16 bit per channel are rounded to 8 bit per channel.
+    /// The palette is in the order of first appearance from the top left row by row,
+    /// so the same image always gives the same palette and buffer.
+    /// An indexed PNG or BMP keeps the order of its palette and its indices as is.
+    #[inline]
     pub fn from_image(image_buffer: &[u8]) -> Result<PixelArt, Error> {
-        let format = image::guess_format(image_buffer)?;
+        Self::from_image_with(image_buffer, ImageOptions::default())
+    }
+
+    /// Creates Bitmap from image file according to `options`.
+    pub fn from_image_with(image_buffer: &[u8], options: ImageOptions) -> Result<PixelArt, Error> {
+        let format = match image::guess_format(image_buffer) {
+            Ok(format) => format,
+            // TGA has no magic number, so it is the last resort.
+            Err(_) if cfg!(feature = "extra-formats") => image::ImageFormat::Tga,
+            Err(e) => return Err(e.into()),
+        };
         match format {
             image::ImageFormat::Png => {}
             image::ImageFormat::Bmp => {}
             image::ImageFormat::Gif => {}
+            #[cfg(feature = "extra-formats")]
+            image::ImageFormat::Jpeg | image::ImageFormat::WebP | image::ImageFormat::Tga => {}
             _ => return Err(Error::UnsupportedImageFormat),
         }
+        options.validate()?;
+        if format == image::ImageFormat::Png && options.alpha_bits.is_none() {
+            if let Some(pixel_art) = Self::from_indexed_png(image_buffer, options)? {
+                return Ok(pixel_art);
+            }
+        }
+        if format == image::ImageFormat::Bmp && options.alpha_bits.is_none() {
+            if let Some(pixel_art) = Self::from_indexed_bmp(image_buffer) {
+                return Ok(pixel_art);
+            }
+        }
         let v = image::load_from_memory_with_format(image_buffer, format)?;
         let size = [v.width(), v.height()];
-        let v = v.into_rgba8().into_raw();
-        let mut col2idx = HashMap::new();
-        let buffer: Vec<_> = v
-            .chunks(4)
-            .map(|e| {
-                let idx = col2idx.len();
-                *col2idx
-                    .entry(u32::from_be_bytes([0, e[0], e[1], e[2]]))
-                    .or_insert(idx as u32)
+        if size.contains(&0) {
+            return Err(Error::EmptyImage);
+        }
+        use image::ColorType::*;
+        let rgba = match v.color() {
+            L16 | La16 | Rgb16 | Rgba16 => rgba16_to_rgba8(&v.into_rgba16().into_raw()),
+            _ => v.into_rgba8().into_raw(),
+        };
+        Ok(Self::from_rgba8(size, &rgba, options))
+    }
+
+    /// Creates Bitmap from the frame `frame` of an animated GIF according to `options`,
+    /// e.g. to extract one pose of a sprite. The frame is composited over the previous ones
+    /// by the disposal methods, so it looks the same as in the animation.
+    pub fn from_gif_frame(
+        image_buffer: &[u8],
+        frame: usize,
+        options: ImageOptions,
+    ) -> Result<PixelArt, Error> {
+        options.validate()?;
+        let mut frames = gif_frames(image_buffer)?;
+        let frames_len = frames.len();
+        if frame >= frames_len {
+            return Err(Error::FrameOutOfRange {
+                frame,
+                frames: frames_len,
+            });
+        }
+        let image = frames.swap_remove(frame);
+        let size = [image.width(), image.height()];
+        if size.contains(&0) {
+            return Err(Error::EmptyImage);
+        }
+        Ok(Self::from_rgba8(size, &image.into_raw(), options))
+    }
+
+    /// Creates Bitmap laying out all the frames of an animated GIF from left to right in `cols` columns,
+    /// then from top to bottom, so the frame `n` starts at `(n % cols * width, n / cols * height)`.
+    /// The frames are composited as in `from_gif_frame` and share one palette.
+    /// `cols` is clamped to `1..=frames`, and the empty cells of the last row are transparent black.
+    pub fn from_gif_sheet(
+        image_buffer: &[u8],
+        cols: usize,
+        options: ImageOptions,
+    ) -> Result<PixelArt, Error> {
+        options.validate()?;
+        let frames = gif_frames(image_buffer)?;
+        let Some(first) = frames.first() else {
+            return Err(Error::EmptyImage);
+        };
+        let [width, height] = [first.width(), first.height()].map(|x| x as usize);
+        if width == 0 || height == 0 {
+            return Err(Error::EmptyImage);
+        }
+        let cols = cols.clamp(1, frames.len());
+        let rows = frames.len().div_ceil(cols);
+        let frames: Vec<Vec<u8>> = frames.into_iter().map(|frame| frame.into_raw()).collect();
+        let sheet_width = width * cols;
+        let mut rgba = vec![0; sheet_width * height * rows * 4];
+        frames.iter().enumerate().for_each(|(n, frame)| {
+            let (left, top) = (n % cols * width, n / cols * height);
+            frame.chunks(width * 4).enumerate().for_each(|(y, row)| {
+                let start = ((top + y) * sheet_width + left) * 4;
+                rgba[start..start + width * 4].copy_from_slice(row);
+            });
+        });
+        let size = [sheet_width as u32, (height * rows) as u32];
+        Ok(Self::from_rgba8(size, &rgba, options))
+    }
+
+    /// Reads the palette and the indices of an indexed PNG directly, including the unused entries.
+    /// Returns `None` for the other color types, and for the ones `from_rgba8` has to handle,
+    /// i.e. a broken index or more than one transparent entry by `options`.
+    fn from_indexed_png(
+        image_buffer: &[u8],
+        options: ImageOptions,
+    ) -> Result<Option<PixelArt>, Error> {
+        let mut decoder = png::Decoder::new(image_buffer);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info()?;
+        let info = reader.info();
+        let (Some(plte), png::ColorType::Indexed) = (&info.palette, info.color_type) else {
+            return Ok(None);
+        };
+        let trns = info.trns.as_deref().unwrap_or_default();
+        let mut palette: Vec<u32> = (plte.chunks_exact(3).enumerate())
+            .map(|(i, rgb)| {
+                let transparency = match options.palette_alpha {
+                    true => 0xff - trns.get(i).copied().unwrap_or(0xff),
+                    false => 0,
+                };
+                u32::from_be_bytes([transparency, rgb[0], rgb[1], rgb[2]])
             })
             .collect();
-        let mut palette = vec![0; col2idx.len()];
-        col2idx
-            .into_iter()
-            .for_each(|(idx, i)| palette[i as usize] = idx);
-        Ok(PixelArt {
+        let mut transparent = (0..palette.len())
+            .filter(|i| {
+                trns.get(*i)
+                    .is_some_and(|alpha| *alpha < options.alpha_threshold)
+            })
+            .map(|i| i as u32);
+        let transparent = match (options.transparency, transparent.next(), transparent.next()) {
+            (false, _, _) | (true, None, _) => None,
+            (true, Some(idx), None) => Some(idx),
+            (true, Some(_), Some(_)) => return Ok(None),
+        };
+        // `0x000000` as the transparent entry of `index_transparent`
+        if let Some(idx) = transparent {
+            palette[idx as usize] = 0;
+        }
+        let [width, height] = [info.width, info.height];
+        if width == 0 || height == 0 {
+            return Err(Error::EmptyImage);
+        }
+        let bit_depth = info.bit_depth as usize;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let line_size = reader.next_frame(&mut data)?.line_size;
+        let buffer: Vec<u32> = (data.chunks(line_size).take(height as usize))
+            .flat_map(|row| unpack_row(row, width as usize, bit_depth))
+            .collect();
+        if buffer.iter().any(|idx| *idx as usize >= palette.len()) {
+            return Ok(None);
+        }
+        Ok(Some(PixelArt {
             palette,
             buffer,
-            size,
+            size: [width, height],
+            transparent,
+            alpha: None,
+        }))
+    }
+
+    /// Reads the palette and the indices of an uncompressed 1, 4 or 8 bit BMP directly,
+    /// including the unused entries. Returns `None` for the other BMPs, which `image` decodes.
+    fn from_indexed_bmp(image_buffer: &[u8]) -> Option<PixelArt> {
+        let u16_at = |i: usize| {
+            Some(u16::from_le_bytes(
+                image_buffer.get(i..i + 2)?.try_into().ok()?,
+            ))
+        };
+        let u32_at = |i: usize| {
+            Some(u32::from_le_bytes(
+                image_buffer.get(i..i + 4)?.try_into().ok()?,
+            ))
+        };
+        let data_offset = u32_at(10)? as usize;
+        let header_size = u32_at(14)? as usize;
+        let (width, height) = (u32_at(18)? as i32, u32_at(22)? as i32);
+        let bit_depth = u16_at(28)? as usize;
+        // `BI_RGB`, i.e. no compression and no bit fields
+        let compression = u32_at(30)?;
+        if header_size < 40 || !matches!(bit_depth, 1 | 4 | 8) || compression != 0 {
+            return None;
+        }
+        // a negative height means the rows are stored from the top.
+        let (width, top_down) = (width as u32, height < 0);
+        let height = height.unsigned_abs();
+        if width == 0 || height == 0 || width > i32::MAX as u32 {
+            return None;
+        }
+        let palette_len = match u32_at(46)? {
+            0 => 1 << bit_depth,
+            len => len as usize,
+        };
+        // the header fields are not trusted, and `usize` may be 32 bit on wasm.
+        let palette_start = header_size.checked_add(14)?;
+        let palette_end = palette_start.checked_add(palette_len.checked_mul(4)?)?;
+        let palette: Vec<u32> = image_buffer
+            .get(palette_start..palette_end)?
+            .chunks_exact(4)
+            .map(|bgr| u32::from_be_bytes([0, bgr[2], bgr[1], bgr[0]]))
+            .collect();
+        // each row is padded to 4 bytes.
+        let row_size = (width as usize).checked_mul(bit_depth)?.div_ceil(32) * 4;
+        let data_end = data_offset.checked_add(row_size.checked_mul(height as usize)?)?;
+        let data = image_buffer.get(data_offset..data_end)?;
+        let rows: Vec<&[u8]> = match top_down {
+            true => data.chunks(row_size).collect(),
+            false => data.chunks(row_size).rev().collect(),
+        };
+        let buffer: Vec<u32> = (rows.into_iter())
+            .flat_map(|row| unpack_row(row, width as usize, bit_depth))
+            .collect();
+        if buffer.iter().any(|idx| *idx as usize >= palette.len()) {
+            return None;
+        }
+        assert_eq!(buffer.len(), width as usize * height as usize);
+        Some(PixelArt {
+            palette,
+            buffer,
+            size: [width, height],
+            transparent: None,
+            alpha: None,
         })
     }
 
+    fn from_rgba8(size: [u32; 2], rgba: &[u8], options: ImageOptions) -> PixelArt {
+        let (palette, buffer) = index_colors(rgba, options.palette_alpha);
+        let (palette, buffer, transparent) = match options.transparency {
+            true => index_transparent(rgba, &palette, &buffer, options.alpha_threshold),
+            false => (palette, buffer, None),
+        };
+        let alpha = options
+            .alpha_bits
+            .map(|bits| AlphaBuffer::from_rgba8(rgba, bits));
+        PixelArt {
+            palette,
+            buffer,
+            size,
+            transparent,
+            alpha,
+        }
+    }
+
+    /// colors `0xRRGGBB` of the palette, with `TT` of `0xTTRRGGBB` by `ImageOptions::palette_alpha`
     #[inline]
     pub fn palette(&self) -> &Vec<u32> {
         &self.palette
@@ -176,41 +1347,1983 @@ impl PixelArt {
         &self.buffer
     }
 
+    /// `[width, height]` of the image
     #[inline]
-    pub fn display(&self, config: DisplayConfig) -> Result<Display<'_>, Error> {
-        if config.inline_level == InlineLevel::Geekest
-            && (config.palette_format != PaletteFormat::RGBFloat
-                || config.buffer_format.force_to_raw)
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// palette index of the transparent pixels, if loaded with `ImageOptions::transparency`
+    #[inline]
+    pub fn transparent_index(&self) -> Option<u32> {
+        self.transparent
+    }
+
+    /// alpha of each pixel, if loaded with `ImageOptions::alpha_bits`
+    #[inline]
+    pub fn alpha(&self) -> Option<&AlphaBuffer> {
+        self.alpha.as_ref()
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.size[0]
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.size[1]
+    }
+
+    /// Color `0xRRGGBB` of the pixel at `(x, y)` from the top left, or `None` if out of the image.
+    /// It is `0xTTRRGGBB` as in the palette by `ImageOptions::palette_alpha`.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<u32> {
+        let [width, height] = self.size;
+        if x >= width || y >= height {
+            return None;
+        }
+        let idx = self.buffer[(y * width + x) as usize];
+        Some(self.palette[idx as usize])
+    }
+
+    /// Paints the pixel at `(x, y)` from the top left with `0xTTRRGGBB`, where `TT` is `255 - alpha` as in the palette,
+    /// so `0xRRGGBB` is opaque. The alpha buffer is made opaque at the pixel.
+    /// The color is added to the palette if it is new, unless the palette would exceed `PALETTE_SIZE_LIMIT`.
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: u32) -> Result<(), Error> {
+        let [width, height] = self.size;
+        if x >= width || y >= height {
+            return Err(Error::PixelOutOfRange {
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+        let transparent = self.transparent.map(|idx| idx as usize);
+        let idx = match (self.palette.iter().enumerate())
+            .position(|(i, c)| *c == color && Some(i) != transparent)
         {
-            Err(Error::NotMeetGeekest)
-        } else {
-            Ok(Display {
-                entity: self,
-                config,
+            Some(idx) => idx,
+            None if self.palette.len() >= PALETTE_SIZE_LIMIT => {
+                return Err(Error::PaletteLengthOver {
+                    limit: PALETTE_SIZE_LIMIT,
+                    palette_len: self.palette.len() + 1,
+                })
+            }
+            None => {
+                self.palette.push(color);
+                self.palette.len() - 1
+            }
+        };
+        self.buffer[(y * width + x) as usize] = idx as u32;
+        if let Some(alpha) = &mut self.alpha {
+            alpha.levels[(y * width + x) as usize] = (1 << alpha.bits) - 1;
+        }
+        Ok(())
+    }
+
+    /// Changes the color of palette entry `index` to `0xRRGGBB`, so every pixel referring to it is recolored.
+    /// The `TT` of the entry by `ImageOptions::palette_alpha` is kept.
+    pub fn set_palette_color(&mut self, index: usize, color: u32) -> Result<(), Error> {
+        let len = self.palette.len();
+        let entry = self
+            .palette
+            .get_mut(index)
+            .ok_or(Error::PaletteIndexOutOfRange { index, len })?;
+        *entry = *entry & 0xff000000 | color & 0xffffff;
+        Ok(())
+    }
+
+    /// Recolors every pixel of the color `from` to the color `to`, both `0xRRGGBB`.
+    /// The `TT` of each entry by `ImageOptions::palette_alpha` is kept.
+    /// If `to` with the same `TT` is already in the palette, the entries of `from` are merged into it and removed.
+    /// The transparent entry is left as is. Returns whether anything has changed.
+    pub fn replace_color(&mut self, from: u32, to: u32) -> bool {
+        let (from, to) = (from & 0xffffff, to & 0xffffff);
+        let len = self.palette.len();
+        let is_color =
+            |i: usize, color: u32| Some(i as u32) != self.transparent && self.palette[i] == color;
+        let sources: Vec<usize> = (0..len)
+            .filter(|i| is_color(*i, self.palette[*i] & 0xff000000 | from))
+            .collect();
+        if from == to || sources.is_empty() {
+            return false;
+        }
+        let targets: Vec<(usize, u32, Option<usize>)> = (sources.iter())
+            .map(|i| {
+                let color = self.palette[*i] & 0xff000000 | to;
+                (*i, color, (0..len).find(|j| is_color(*j, color)))
+            })
+            .collect();
+        let mut merged = Vec::new();
+        targets
+            .into_iter()
+            .for_each(|(i, color, target)| match target {
+                Some(target) => merged.push((i, target)),
+                None => self.palette[i] = color,
+            });
+        if merged.is_empty() {
+            return true;
+        }
+        let mut palette = Vec::new();
+        let mut old2new: Vec<u32> = (self.palette.iter().enumerate())
+            .map(
+                |(i, color)| match merged.iter().any(|(source, _)| *source == i) {
+                    true => 0,
+                    false => {
+                        palette.push(*color);
+                        palette.len() as u32 - 1
+                    }
+                },
+            )
+            .collect();
+        merged
+            .iter()
+            .for_each(|(source, target)| old2new[*source] = old2new[*target]);
+        self.palette = palette;
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+        self.transparent = self.transparent.map(|idx| old2new[idx as usize]);
+        true
+    }
+
+    /// Swaps palette entries `a` and `b` together with the indices in the buffer, so the image does not change.
+    pub fn swap_palette_index(&mut self, a: usize, b: usize) -> Result<(), Error> {
+        let len = self.palette.len();
+        if let Some(index) = [a, b].into_iter().find(|index| *index >= len) {
+            return Err(Error::PaletteIndexOutOfRange { index, len });
+        }
+        self.palette.swap(a, b);
+        let (a, b) = (a as u32, b as u32);
+        self.transparent = self.transparent.map(|idx| match idx {
+            _ if idx == a => b,
+            _ if idx == b => a,
+            _ => idx,
+        });
+        self.buffer.iter_mut().for_each(|idx| {
+            if *idx == a {
+                *idx = b;
+            } else if *idx == b {
+                *idx = a;
+            }
+        });
+        Ok(())
+    }
+
+    /// Shifts the palette entries in `start..end` cyclically by `by`, i.e. the entry `i` moves to
+    /// `start + (i - start + by) mod (end - start)`, and remaps the buffer so the image does not change.
+    /// Negative `by` shifts to the smaller indices. A building block of color cycling.
+    pub fn rotate_palette(&mut self, start: usize, end: usize, by: i32) -> Result<(), Error> {
+        let len = self.palette.len();
+        if start > end || end > len {
+            return Err(Error::InvalidPaletteRange { start, end, len });
+        }
+        let range_len = end - start;
+        if range_len == 0 {
+            return Ok(());
+        }
+        let by = (by as i64).rem_euclid(range_len as i64) as usize;
+        let old2new: Vec<u32> = (0..len)
+            .map(|i| match (start..end).contains(&i) {
+                true => (start + (i - start + by) % range_len) as u32,
+                false => i as u32,
+            })
+            .collect();
+        self.palette[start..end].rotate_right(by);
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+        self.transparent = self.transparent.map(|idx| old2new[idx as usize]);
+        Ok(())
+    }
+
+    /// Counts how many pixels refer to each palette index.
+    pub fn color_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![0; self.palette.len()];
+        self.buffer
+            .iter()
+            .for_each(|idx| histogram[*idx as usize] += 1);
+        histogram
+    }
+
+    /// Sorts the palette so that the most used colors have the smallest indices.
+    /// Colors used the same number of times keep their relative order. The image itself does not change.
+    pub fn sort_palette_by_frequency(&mut self) {
+        let histogram = self.color_histogram();
+        let mut order: Vec<usize> = (0..self.palette.len()).collect();
+        order.sort_by_key(|i| std::cmp::Reverse(histogram[*i]));
+        let mut old2new = vec![0; order.len()];
+        order
+            .iter()
+            .enumerate()
+            .for_each(|(new, old)| old2new[*old] = new as u32);
+        self.palette = order.iter().map(|old| self.palette[*old]).collect();
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+        self.transparent = self.transparent.map(|idx| old2new[idx as usize]);
+    }
+
+    /// Drops the palette entries no pixel refers to, keeping the order of the others.
+    /// The image does not change, and the bit width may shrink.
+    pub fn remove_unused_palette(&mut self) {
+        let histogram = self.color_histogram();
+        let mut old2new = vec![0; self.palette.len()];
+        let mut new_len = 0;
+        histogram.iter().enumerate().for_each(|(old, count)| {
+            if *count > 0 {
+                old2new[old] = new_len;
+                self.palette[new_len as usize] = self.palette[old];
+                new_len += 1;
+            }
+        });
+        self.palette.truncate(new_len as usize);
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+        self.transparent = self
+            .transparent
+            .filter(|idx| histogram[*idx as usize] > 0)
+            .map(|idx| old2new[idx as usize]);
+    }
+
+    /// Creates the pixel art of `size` whose pixels are the ones of this image at `positions` in the buffer.
+    /// The palette has only the used colors in the order of first appearance.
+    fn reindexed(&self, size: [u32; 2], positions: &[usize]) -> PixelArt {
+        let mut old2new = HashMap::new();
+        let mut palette = Vec::new();
+        let buffer = positions
+            .iter()
+            .map(|i| {
+                let old = self.buffer[*i];
+                *old2new.entry(old).or_insert_with(|| {
+                    palette.push(self.palette[old as usize]);
+                    palette.len() as u32 - 1
+                })
+            })
+            .collect();
+        let transparent = self.transparent.and_then(|idx| old2new.get(&idx).copied());
+        let alpha = self.alpha.as_ref().map(|alpha| AlphaBuffer {
+            bits: alpha.bits,
+            levels: positions.iter().map(|i| alpha.levels[*i]).collect(),
+        });
+        PixelArt {
+            palette,
+            buffer,
+            size,
+            transparent,
+            alpha,
+        }
+    }
+
+    /// Applies `f` to the buffer and the alpha levels alike, which are in the same order.
+    fn for_each_layer(&mut self, f: impl Fn(&mut Vec<u32>)) {
+        f(&mut self.buffer);
+        if let Some(alpha) = &mut self.alpha {
+            f(&mut alpha.levels);
+        }
+    }
+
+    /// Cuts out the `w`x`h` region whose top left is `(x, y)`, e.g. one sprite of a sprite sheet.
+    /// The palette of the result has only the colors in the region.
+    pub fn crop(&self, x: u32, y: u32, w: u32, h: u32) -> Result<PixelArt, Error> {
+        let [width, height] = self.size;
+        if w == 0 || h == 0 {
+            return Err(Error::EmptyImage);
+        }
+        if x as u64 + w as u64 > width as u64 || y as u64 + h as u64 > height as u64 {
+            return Err(Error::RegionOutOfRange {
+                x,
+                y,
+                region_width: w,
+                region_height: h,
+                width,
+                height,
+            });
+        }
+        let positions: Vec<usize> = (y..y + h)
+            .flat_map(|y| (x..x + w).map(move |x| (y * width + x) as usize))
+            .collect();
+        Ok(self.reindexed([w, h], &positions))
+    }
+
+    /// Resamples the image to `w`x`h` by nearest neighbor, taking the pixel at the center of each new pixel,
+    /// so scaling by an integer factor and back is lossless. The palette is rebuilt with only the used colors.
+    pub fn resize_nearest(&mut self, w: u32, h: u32) -> Result<(), Error> {
+        if w == 0 || h == 0 {
+            return Err(Error::EmptyImage);
+        }
+        let [width, height] = self.size.map(|x| x as u64);
+        // the center of the new pixel `i` is `(i + 0.5) * old / new` in the old image.
+        let nearest =
+            |i: u32, old: u64, new: u32| ((2 * i as u64 + 1) * old / (2 * new as u64)) as usize;
+        let positions: Vec<usize> = (0..h)
+            .flat_map(|y| {
+                let row = nearest(y, height, h) * width as usize;
+                (0..w).map(move |x| row + nearest(x, width, w))
             })
+            .collect();
+        let resized = self.reindexed([w, h], &positions);
+        *self = resized;
+        Ok(())
+    }
+
+    /// Mirrors the image left to right. The palette does not change.
+    pub fn flip_horizontal(&mut self) {
+        let width = self.size[0] as usize;
+        self.for_each_layer(|layer| layer.chunks_mut(width).for_each(|row| row.reverse()));
+    }
+
+    /// Mirrors the image upside down. The palette does not change.
+    /// Note that the flipped image displayed with `reverse_rows = false`
+    /// draws the same picture as the original image displayed with `reverse_rows = true`.
+    pub fn flip_vertical(&mut self) {
+        let width = self.size[0] as usize;
+        self.for_each_layer(|layer| {
+            *layer = layer.chunks(width).rev().flatten().copied().collect()
+        });
+    }
+
+    /// Rotates the image clockwise by `quarter_turns` times 90 degrees, taken modulo 4.
+    /// The width and the height are swapped for odd turns. The palette does not change.
+    pub fn rotate(&mut self, quarter_turns: u8) {
+        let [width, height] = self.size.map(|x| x as usize);
+        match quarter_turns % 4 {
+            0 => {}
+            2 => self.for_each_layer(|layer| layer.reverse()),
+            turns => {
+                // the new pixel `(x, y)` is the old pixel `(y, height - 1 - x)` for a clockwise turn
+                self.for_each_layer(|layer| {
+                    *layer = (0..width)
+                        .flat_map(|y| {
+                            (0..height).map(move |x| match turns {
+                                1 => (height - 1 - x) * width + y,
+                                _ => x * width + width - 1 - y,
+                            })
+                        })
+                        .map(|i| layer[i])
+                        .collect()
+                });
+                self.size = [height as u32, width as u32];
+            }
         }
     }
 
-    /// necessary bit shift for represent pixel
+    /// Merges the identical colors of the palette into the first of them, e.g. after `set_palette_color`.
+    /// The palette keeps the order of the remaining entries, and the image does not change.
+    /// The transparent entry is never merged. Unused entries remain, see `remove_unused_palette`.
+    pub fn dedup_palette(&mut self) {
+        let mut col2idx = HashMap::new();
+        let mut palette = Vec::new();
+        let old2new: Vec<u32> = (self.palette.iter().enumerate())
+            .map(|(old, color)| {
+                // the transparent entry is keyed apart from the colors
+                let key = match Some(old as u32) == self.transparent {
+                    true => None,
+                    false => Some(*color),
+                };
+                *col2idx.entry(key).or_insert_with(|| {
+                    palette.push(*color);
+                    palette.len() as u32 - 1
+                })
+            })
+            .collect();
+        self.palette = palette;
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+        self.transparent = self.transparent.map(|idx| old2new[idx as usize]);
+    }
+
+    /// Swaps the red and blue channels of every palette entry, e.g. to convert BGR colors to RGB.
+    /// The buffer and the `TT` of `0xTTRRGGBB` are not changed.
+    pub fn swap_rb(&mut self) {
+        self.palette.iter_mut().for_each(|color| {
+            let [t, r, g, b] = color.to_be_bytes();
+            *color = u32::from_be_bytes([t, b, g, r]);
+        });
+    }
+
+    /// Colors `0xRRGGBB` of the pixels, row by row from the top left.
+    /// They are `0xTTRRGGBB` as in the palette by `ImageOptions::palette_alpha`.
+    pub fn pixels(&self) -> impl Iterator<Item = u32> + '_ {
+        self.buffer.iter().map(|idx| self.palette[*idx as usize])
+    }
+
+    /// Colors of the pixels with the coordinates `(x, y)`, where `(0, 0)` is the top left.
+    pub fn pixels_xy(&self) -> impl Iterator<Item = ((u32, u32), u32)> + '_ {
+        let width = self.size[0];
+        self.pixels()
+            .enumerate()
+            .map(move |(i, color)| ((i as u32 % width, i as u32 / width), color))
+    }
+
+    /// Reconstructs the raw RGBA8 pixels, row by row from the top left.
+    /// Alpha is `0` for the transparent pixels, and the alpha in the palette times the alpha buffer,
+    /// each `0xff` if none, for the others.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.buffer
+            .iter()
+            .enumerate()
+            .flat_map(|(i, idx)| {
+                let [transparency, r, g, b] = self.palette[*idx as usize].to_be_bytes();
+                let alpha = (0xff - transparency) as u32;
+                let alpha = match &self.alpha {
+                    Some(buffer) => (alpha * buffer.alpha8(buffer.levels[i]) as u32 + 127) / 255,
+                    None => alpha,
+                } as u8;
+                match Some(*idx) == self.transparent {
+                    true => [r, g, b, 0],
+                    false => [r, g, b, alpha],
+                }
+            })
+            .collect()
+    }
+
+    /// Reconstructs the image and encodes it as a RGBA PNG file.
+    pub fn to_image(&self) -> Result<Vec<u8>, Error> {
+        let [width, height] = self.size;
+        let image = image::RgbaImage::from_raw(width, height, self.to_rgba8())
+            .expect("the length of buffer does not match the size");
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(image).write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageOutputFormat::Png,
+        )?;
+        Ok(bytes)
+    }
+
+    /// Encodes the image as a palette-indexed PNG file with the palette and the indices as they are.
+    /// The alpha in the palette is kept, but the alpha buffer is not, since the alpha of an indexed PNG
+    /// belongs to the palette.
+    /// The bit depth is the smallest of 1, 2, 4, and 8 bits that fits the palette.
+    /// Fails if the palette has more than 256 colors.
+    pub fn to_indexed_png(&self) -> Result<Vec<u8>, Error> {
+        if self.palette.len() > 256 {
+            return Err(Error::PaletteLengthOver {
+                limit: 256,
+                palette_len: self.palette.len(),
+            });
+        }
+        let [width, height] = self.size;
+        let bit_depth = self.necessary_bit_shift();
+        let palette: Vec<u8> = self
+            .palette
+            .iter()
+            .flat_map(|color| {
+                let [_, r, g, b] = color.to_be_bytes();
+                [r, g, b]
+            })
+            .collect();
+        // each row starts from a new byte, and the leftmost pixel is in the high-order bits.
+        let data: Vec<u8> = self
+            .buffer
+            .chunks(width as usize)
+            .flat_map(|row| {
+                row.chunks(8 / bit_depth).map(|pixels| {
+                    pixels.iter().enumerate().fold(0, |byte, (i, idx)| {
+                        byte | (*idx as u8) << (8 - bit_depth * (i + 1))
+                    })
+                })
+            })
+            .collect();
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::from_u8(bit_depth as u8).unwrap());
+        encoder.set_palette(palette);
+        let mut trns: Vec<u8> = (self.palette.iter().enumerate())
+            .map(|(i, color)| match Some(i as u32) == self.transparent {
+                true => 0,
+                false => 0xff - color.to_be_bytes()[0],
+            })
+            .collect();
+        while trns.last() == Some(&0xff) {
+            trns.pop();
+        }
+        if !trns.is_empty() {
+            encoder.set_trns(trns);
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    /// Serializes the pixel art as `{"palette":[...],"buffer":[...],"size":[w,h]}`,
+    /// followed by `"transparent":index` if there is the transparent index
+    /// and `"alpha":{"bits":n,"levels":[...]}` if there is the alpha buffer.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("pixel art is always serializable")
+    }
+
+    /// Serializes the pixel art as `to_json` with line breaks and indentation.
+    pub fn to_json_pretty(&self) -> String {
+        serde_json::to_string_pretty(self).expect("pixel art is always serializable")
+    }
+
+    /// Deserializes the pixel art from the json by `to_json` and checks it by `validate`.
+    pub fn from_json(json: &str) -> Result<PixelArt, Error> {
+        let pixel_art: PixelArt = serde_json::from_str(json)?;
+        pixel_art.validate()?;
+        Ok(pixel_art)
+    }
+
+    /// Checks that the image is not empty, the buffer and the alpha buffer have `width * height` pixels,
+    /// every index including the transparent one is in the palette and every alpha level fits its bits,
+    /// which hold for `from_image` but not for deserialized ones.
+    pub fn validate(&self) -> Result<(), Error> {
+        let [width, height] = self.size;
+        if self.size.contains(&0) {
+            return Err(Error::EmptyImage);
+        }
+        if self.buffer.len() as u64 != width as u64 * height as u64 {
+            return Err(Error::BufferLengthMismatch {
+                len: self.buffer.len(),
+                width,
+                height,
+            });
+        }
+        if let Some(alpha) = &self.alpha {
+            if !matches!(alpha.bits, 1 | 2 | 4 | 8) {
+                return Err(Error::InvalidAlphaBits(alpha.bits));
+            }
+            if let Some(level) = alpha.levels.iter().find(|level| **level >> alpha.bits > 0) {
+                return Err(Error::AlphaLevelOutOfRange {
+                    level: *level,
+                    bits: alpha.bits,
+                });
+            }
+            if alpha.levels.len() != self.buffer.len() {
+                return Err(Error::BufferLengthMismatch {
+                    len: alpha.levels.len(),
+                    width,
+                    height,
+                });
+            }
+        }
+        let len = self.palette.len();
+        let mut indices = self.buffer.iter().chain(&self.transparent);
+        match indices.find(|idx| **idx as usize >= len) {
+            Some(index) => Err(Error::PaletteIndexOutOfRange {
+                index: *index as usize,
+                len,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the palette as a GIMP palette file (`.gpl`), in the order of the palette indices.
+    /// Each color is named after its hexadecimal code. The alpha in the palette is dropped.
+    pub fn export_gpl(&self) -> String {
+        let mut gpl = String::from("GIMP Palette\nName: dot2shader\n#\n");
+        self.palette.iter().for_each(|color| {
+            let [_, r, g, b] = color.to_be_bytes();
+            let rgb = color & 0xffffff;
+            gpl += &format!("{r:3} {g:3} {b:3}\t#{rgb:06x}\n");
+        });
+        gpl
+    }
+
     #[inline]
-    fn necessary_bit_shift(&self) -> usize {
-        usize::pow(
-            2,
-            f32::ceil(f32::log2(
-                1.0 + f32::floor(f32::log2(usize::max(self.palette.len() - 1, 1) as f32)),
-            )) as u32,
-        )
+    pub fn display(&self, config: DisplayConfig) -> Result<Display<'_>, Error> {
+        if self.buffer.is_empty() || self.size.contains(&0) {
+            return Err(Error::EmptyImage);
+        }
+        config.validate()?;
+        if let Some(limit) = config.palette_limit {
+            if self.palette.len() > limit {
+                return Err(Error::PaletteLengthOver {
+                    limit,
+                    palette_len: self.palette.len(),
+                });
+            }
+        }
+        if let Some(bit_width) = config.buffer_format.bit_width {
+            let offset = config.index_offset() as usize;
+            if self.palette.len() + offset > 1 << bit_width {
+                return Err(Error::PaletteNotFitBitWidth {
+                    bit_width,
+                    palette_len: self.palette.len(),
+                });
+            }
+        }
+        let mut display = Display {
+            entity: self,
+            config,
+        };
+        if config.buffer_format.encoding == BufferEncoding::Smallest {
+            let buffer_len = |encoding| {
+                let mut display = display;
+                display.config.buffer_format.encoding = encoding;
+                display.compressed_buffer().0.len()
+            };
+            display.config.buffer_format.encoding =
+                match buffer_len(BufferEncoding::RunLength) < buffer_len(BufferEncoding::Packed) {
+                    true => BufferEncoding::RunLength,
+                    false => BufferEncoding::Packed,
+                };
+        }
+        Ok(display)
+    }
+
+    /// Picks the buffer format whose buffer array is the smallest in bytes among the power of two packing,
+    /// the tight packing, the run-length encoding and the raw buffer, with the other fields of `base`.
+    /// The sizes are measured by `Display::stats` without generating the code, and ties go to the former.
+    /// Only the packing fits `InlineLevel::Geekest`.
+    pub fn best_buffer_format(&self, base: BufferFormat) -> BufferFormat {
+        let base = BufferFormat {
+            force_to_raw: false,
+            bit_width: None,
+            tight_packing: false,
+            encoding: BufferEncoding::Packed,
+            ..base
+        };
+        let candidates = [
+            base,
+            BufferFormat {
+                tight_packing: true,
+                ..base
+            },
+            BufferFormat {
+                encoding: BufferEncoding::RunLength,
+                ..base
+            },
+            BufferFormat {
+                force_to_raw: true,
+                ..base
+            },
+        ];
+        let byte_size = |buffer_format: BufferFormat| {
+            let config = DisplayConfig {
+                buffer_format,
+                ..Default::default()
+            };
+            self.display(config)
+                .map_or(usize::MAX, |display| display.stats().byte_size)
+        };
+        candidates
+            .into_iter()
+            .min_by_key(|buffer_format| byte_size(*buffer_format))
+            .unwrap()
+    }
+
+    /// Number of bits per palette index in the compressed buffer, a power of two such that
+    /// `32 / necessary_bit_shift()` indices are packed into one `u32`.
+    #[inline]
+    pub fn necessary_bit_shift(&self) -> usize {
+        self.necessary_bit_width().next_power_of_two()
     }
+    /// necessary bit width for represent pixel without rounding up to a power of two
     #[inline]
-    fn is_compressible(&self) -> bool {
+    fn necessary_bit_width(&self) -> usize {
+        let bits = usize::BITS - self.palette.len().saturating_sub(1).leading_zeros();
+        usize::max(bits as usize, 1)
+    }
+    /// Whether the buffer can be compressed, i.e. the palette has less than `2^16` colors.
+    #[inline]
+    pub fn is_compressible(&self) -> bool {
         self.palette.len() < usize::pow(2, 16)
     }
 }
 
+#[test]
+fn necessary_bit_shift() {
+    let pixel_art = |palette_len: usize| PixelArt {
+        palette: vec![0; palette_len],
+        buffer: vec![0],
+        size: [1, 1],
+        transparent: None,
+        alpha: None,
+    };
+    [
+        (1, 1),
+        (2, 1),
+        (3, 2),
+        (4, 2),
+        (5, 4),
+        (16, 4),
+        (17, 8),
+        (256, 8),
+        (257, 16),
+    ]
+    .into_iter()
+    .for_each(|(palette_len, bit_shift)| {
+        let pixel_art = pixel_art(palette_len);
+        assert_eq!(pixel_art.necessary_bit_shift(), bit_shift, "{palette_len}");
+    });
+}
+
+#[test]
+fn image_round_trip() {
+    let pixel_art = PixelArt::from_image(include_bytes!("../resources/heart.png")).unwrap();
+    let png = pixel_art.to_image().unwrap();
+    assert_eq!(PixelArt::from_image(&png).unwrap(), pixel_art);
+}
+
+#[test]
+fn sixteen_bit_png() {
+    let pixel_art = PixelArt::from_image(include_bytes!("../resources/rgb16.png")).unwrap();
+    assert_eq!(pixel_art.palette(), &vec![0xff0100, 0x808080, 0x0101fe]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 2, 0]);
+}
+
+#[test]
+fn deterministic_palette_order() {
+    let steel = include_bytes!("../resources/steel.png");
+    let pixel_art = PixelArt::from_image(steel).unwrap();
+    let again = PixelArt::from_image(steel).unwrap();
+    assert_eq!(pixel_art.palette(), again.palette());
+    assert_eq!(pixel_art.buffer(), again.buffer());
+    // the order of first appearance, not of the hashes
+    let rgba = [9, 9, 9, 255, 1, 1, 1, 255, 9, 9, 9, 255, 5, 5, 5, 255];
+    let pixel_art = PixelArt::from_rgba8([2, 2], &rgba, ImageOptions::default());
+    assert_eq!(pixel_art.palette(), &vec![0x090909, 0x010101, 0x050505]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 0, 2]);
+}
+
+#[test]
+fn indexed_png_palette() {
+    let png = include_bytes!("../resources/indexed.png");
+    let pixel_art = PixelArt::from_image(png).unwrap();
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0x0000ff, 0xff0000, 0x00ff00, 0xffffff]
+    );
+    assert_eq!(pixel_art.buffer(), &vec![1, 0, 3, 0, 1, 1]);
+    assert_eq!(pixel_art.transparent_index(), None);
+    let options = ImageOptions {
+        transparency: true,
+        ..Default::default()
+    };
+    let pixel_art = PixelArt::from_image_with(png, options).unwrap();
+    assert_eq!(pixel_art.transparent_index(), Some(3));
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0x0000ff, 0xff0000, 0x00ff00, 0x000000]
+    );
+}
+
+#[test]
+fn gif_frame() {
+    let gif = include_bytes!("../resources/frames.gif");
+    let first = PixelArt::from_gif_frame(gif, 0, ImageOptions::default()).unwrap();
+    assert_eq!(first.palette(), &vec![0xff0000, 0x00ff00]);
+    assert_eq!(first.buffer(), &vec![0, 1, 1, 0]);
+    // the second frame updates only the bottom right pixel.
+    let second = PixelArt::from_gif_frame(gif, 1, ImageOptions::default()).unwrap();
+    assert_eq!(second.palette(), &vec![0xff0000, 0x00ff00, 0x0000ff]);
+    assert_eq!(second.buffer(), &vec![0, 1, 1, 2]);
+    assert!(matches!(
+        PixelArt::from_gif_frame(gif, 2, ImageOptions::default()),
+        Err(Error::FrameOutOfRange {
+            frame: 2,
+            frames: 2
+        })
+    ));
+}
+
+#[test]
+fn gif_sheet() {
+    let gif = include_bytes!("../resources/frames.gif");
+    let sheet = PixelArt::from_gif_sheet(gif, 2, ImageOptions::default()).unwrap();
+    assert_eq!(sheet.dimensions(), [4, 2]);
+    assert_eq!(sheet.palette(), &vec![0xff0000, 0x00ff00, 0x0000ff]);
+    assert_eq!(sheet.buffer(), &vec![0, 1, 0, 1, 1, 0, 1, 2]);
+    let column = PixelArt::from_gif_sheet(gif, 0, ImageOptions::default()).unwrap();
+    assert_eq!(column.dimensions(), [2, 4]);
+    assert_eq!(column.buffer(), &vec![0, 1, 1, 0, 0, 1, 1, 2]);
+}
+
+#[test]
+fn indexed_bmp_palette() {
+    let bmp = include_bytes!("../resources/indexed.bmp");
+    let pixel_art = PixelArt::from_indexed_bmp(bmp).unwrap();
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0x0000ff, 0xff0000, 0x00ff00, 0xffffff]
+    );
+    assert_eq!(pixel_art.buffer(), &vec![1, 0, 3, 0, 1, 1]);
+    assert_eq!(pixel_art.dimensions(), [3, 2]);
+    // truecolor
+    let mut rgb = bmp.to_vec();
+    rgb[28] = 24;
+    assert_eq!(PixelArt::from_indexed_bmp(&rgb), None);
+    assert_eq!(PixelArt::from_indexed_bmp(&bmp[..60]), None);
+    // the offsets and the lengths out of the file
+    let mut broken = bmp.to_vec();
+    broken[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(PixelArt::from_indexed_bmp(&broken), None);
+    let mut broken = bmp.to_vec();
+    broken[14..18].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(PixelArt::from_indexed_bmp(&broken), None);
+    let mut broken = bmp.to_vec();
+    broken[46..50].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(PixelArt::from_indexed_bmp(&broken), None);
+    let mut broken = bmp.to_vec();
+    broken[18..22].copy_from_slice(&(i32::MAX as u32).to_le_bytes());
+    broken[22..26].copy_from_slice(&i32::MIN.to_le_bytes());
+    assert_eq!(PixelArt::from_indexed_bmp(&broken), None);
+}
+
+#[test]
+fn indexed_png_round_trip() {
+    let too_many = PixelArt {
+        palette: (0..257).collect(),
+        buffer: (0..257).collect(),
+        size: [257, 1],
+        transparent: None,
+        alpha: None,
+    };
+    assert!(matches!(
+        too_many.to_indexed_png(),
+        Err(Error::PaletteLengthOver { limit: 256, .. })
+    ));
+    let pixel_art = PixelArt::from_image(include_bytes!("../resources/heart.png")).unwrap();
+    let png = pixel_art.to_indexed_png().unwrap();
+    assert!(png.len() < pixel_art.to_image().unwrap().len());
+    assert_eq!(PixelArt::from_image(&png).unwrap(), pixel_art);
+}
+
+#[test]
+fn set_palette_color() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    pixel_art.set_palette_color(1, 0x123456).unwrap();
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x123456]);
+    assert!(matches!(
+        pixel_art.set_palette_color(2, 0),
+        Err(Error::PaletteIndexOutOfRange { index: 2, len: 2 })
+    ));
+    // the alpha in the palette is kept
+    pixel_art.palette[0] = 0x7fff0000;
+    pixel_art.set_palette_color(0, 0xff0000ff).unwrap();
+    assert_eq!(pixel_art.palette(), &vec![0x7f0000ff, 0x123456]);
+}
+
+#[test]
+fn swap_palette_index() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let rgba = pixel_art.to_rgba8();
+    pixel_art.swap_palette_index(0, 2).unwrap();
+    assert_eq!(pixel_art.palette(), &vec![0x0000ff, 0x00ff00, 0xff0000]);
+    assert_eq!(pixel_art.buffer(), &vec![2, 1, 0, 2]);
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+    assert!(pixel_art.swap_palette_index(1, 3).is_err());
+}
+
+#[test]
+fn rotate_palette() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0x000000, 0xff0000, 0x00ff00, 0x0000ff, 0xffffff],
+        buffer: vec![0, 1, 2, 3, 4, 1],
+        size: [3, 2],
+        transparent: Some(3),
+        alpha: None,
+    };
+    let rgba = pixel_art.to_rgba8();
+    pixel_art.rotate_palette(1, 4, 1).unwrap();
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0x000000, 0x0000ff, 0xff0000, 0x00ff00, 0xffffff]
+    );
+    assert_eq!(pixel_art.buffer(), &vec![0, 2, 3, 1, 4, 2]);
+    assert_eq!(pixel_art.transparent_index(), Some(1));
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+    pixel_art.rotate_palette(1, 4, -4).unwrap();
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 2, 3, 4, 1]);
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+    assert!(matches!(
+        pixel_art.rotate_palette(3, 6, 1),
+        Err(Error::InvalidPaletteRange { end: 6, len: 5, .. })
+    ));
+    assert!(pixel_art.rotate_palette(3, 2, 1).is_err());
+}
+
+#[test]
+fn replace_color() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff, 0x00ff00],
+        buffer: vec![0, 1, 2, 3, 0, 1],
+        size: [3, 2],
+        transparent: Some(2),
+        alpha: None,
+    };
+    assert!(!pixel_art.replace_color(0x123456, 0xffffff));
+    assert!(!pixel_art.replace_color(0xff0000, 0xff0000));
+    assert!(pixel_art.replace_color(0xff0000, 0xffffff));
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0xffffff, 0x00ff00, 0x0000ff, 0x00ff00]
+    );
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 2, 3, 0, 1]);
+    // the transparent entry is neither replaced nor merged into
+    assert!(!pixel_art.replace_color(0x0000ff, 0xffffff));
+    assert!(pixel_art.replace_color(0x00ff00, 0xffffff));
+    assert_eq!(pixel_art.palette(), &vec![0xffffff, 0x0000ff]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 0, 1, 0, 0, 0]);
+    assert_eq!(pixel_art.transparent_index(), Some(1));
+    // the alpha in the palette is kept, and only the same alpha is merged into
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x7fff0000, 0x7f00ff00, 0x00ff00],
+        buffer: vec![0, 1, 2, 3],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let rgba = pixel_art.to_rgba8();
+    assert!(pixel_art.replace_color(0xff0000, 0x00ff00));
+    assert_eq!(pixel_art.palette(), &vec![0x7f00ff00, 0x00ff00]);
+    assert_eq!(pixel_art.buffer(), &vec![1, 0, 0, 1]);
+    assert_eq!(pixel_art.to_rgba8()[7], rgba[7]);
+    assert!(pixel_art.replace_color(0x00ff00, 0x0000ff));
+    assert_eq!(pixel_art.palette(), &vec![0x7f0000ff, 0x0000ff]);
+}
+
+#[test]
+fn sort_palette_by_frequency() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 1, 2, 1, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let rgba = pixel_art.to_rgba8();
+    assert_eq!(pixel_art.color_histogram(), vec![1, 3, 2]);
+    pixel_art.sort_palette_by_frequency();
+    assert_eq!(pixel_art.palette(), &vec![0x00ff00, 0x0000ff, 0xff0000]);
+    assert_eq!(pixel_art.buffer(), &vec![2, 0, 0, 1, 0, 1]);
+    assert_eq!(pixel_art.color_histogram(), vec![3, 2, 1]);
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+}
+
+#[test]
+fn validate_pixel_art() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    assert!(pixel_art.validate().is_ok());
+    pixel_art.buffer[3] = 2;
+    assert!(matches!(
+        pixel_art.validate(),
+        Err(Error::PaletteIndexOutOfRange { index: 2, len: 2 })
+    ));
+    pixel_art.buffer.pop();
+    assert!(matches!(
+        pixel_art.validate(),
+        Err(Error::BufferLengthMismatch { len: 3, .. })
+    ));
+    pixel_art.size = [0, 2];
+    assert!(matches!(pixel_art.validate(), Err(Error::EmptyImage)));
+}
+
+#[test]
+fn json_round_trip() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let json = pixel_art.to_json();
+    assert_eq!(
+        json,
+        r#"{"palette":[16711680,65280],"buffer":[0,1,1,0],"size":[2,2]}"#
+    );
+    assert_eq!(PixelArt::from_json(&json).unwrap(), pixel_art);
+    let pretty = pixel_art.to_json_pretty();
+    assert!(pretty.starts_with("{\n  \"palette\": [\n    16711680,\n"));
+    assert_eq!(PixelArt::from_json(&pretty).unwrap(), pixel_art);
+    assert!(matches!(
+        PixelArt::from_json(r#"{"palette":[0],"buffer":[0,1],"size":[2,1]}"#),
+        Err(Error::PaletteIndexOutOfRange { index: 1, len: 1 })
+    ));
+    assert!(matches!(
+        PixelArt::from_json(r#"{"palette":[0],"buffer":[0,0],"size":[2,1],"transparent":1}"#),
+        Err(Error::PaletteIndexOutOfRange { index: 1, len: 1 })
+    ));
+    assert!(matches!(
+        PixelArt::from_json(r#"{"palette":[0],"buffer":[0,0],"size":[1,1]}"#),
+        Err(Error::BufferLengthMismatch { len: 2, .. })
+    ));
+    assert!(matches!(PixelArt::from_json("{"), Err(Error::JsonError(_))));
+}
+
+#[test]
+fn pixels() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00],
+        buffer: vec![0, 1, 1, 1, 0, 0],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    assert_eq!(
+        pixel_art.pixels().collect::<Vec<_>>(),
+        vec![0xff0000, 0x00ff00, 0x00ff00, 0x00ff00, 0xff0000, 0xff0000]
+    );
+    let last = pixel_art.pixels_xy().last().unwrap();
+    assert_eq!(last, ((2, 1), 0xff0000));
+    assert_eq!(pixel_art.pixels_xy().nth(3).unwrap(), ((0, 1), 0x00ff00));
+}
+
+#[test]
+fn export_gpl() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff8000, 0x123456],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let gpl = pixel_art.export_gpl();
+    assert!(gpl.starts_with("GIMP Palette\n"));
+    assert!(gpl.ends_with("255 128   0\t#ff8000\n 18  52  86\t#123456\n"));
+    // the alpha in the palette is dropped
+    let pixel_art = PixelArt {
+        palette: vec![0x7fff8000],
+        ..pixel_art
+    };
+    assert!(pixel_art.export_gpl().ends_with("255 128   0\t#ff8000\n"));
+}
+
+#[test]
+fn set_pixel() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    assert_eq!(pixel_art.get_pixel(1, 0), Some(0x00ff00));
+    assert_eq!(pixel_art.get_pixel(2, 0), None);
+    pixel_art.set_pixel(1, 1, 0x00ff00).unwrap();
+    assert_eq!(pixel_art.palette().len(), 2);
+    pixel_art.set_pixel(0, 1, 0x0000ff).unwrap();
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x00ff00, 0x0000ff]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 2, 1]);
+    assert!(matches!(
+        pixel_art.set_pixel(0, 2, 0),
+        Err(Error::PixelOutOfRange { x: 0, y: 2, .. })
+    ));
+    // the color takes the alpha in the palette
+    pixel_art.palette[1] = 0x7f00ff00;
+    pixel_art.set_pixel(0, 0, 0x7f00ff00).unwrap();
+    pixel_art.set_pixel(1, 0, 0x00ff00).unwrap();
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0xff0000, 0x7f00ff00, 0x0000ff, 0x00ff00]
+    );
+    assert_eq!(pixel_art.buffer(), &vec![1, 3, 2, 1]);
+}
+
+#[test]
+fn remove_unused_palette() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0x000000, 0xff0000, 0x00ff00, 0x0000ff, 0xffffff],
+        buffer: vec![1, 3, 3, 1],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    assert_eq!(pixel_art.necessary_bit_shift(), 4);
+    let rgba = pixel_art.to_rgba8();
+    pixel_art.remove_unused_palette();
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x0000ff]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 1, 0]);
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+    assert_eq!(pixel_art.necessary_bit_shift(), 1);
+}
+
+#[test]
+fn dedup_palette() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0xff0000, 0x0000ff, 0x00ff00, 0x000000],
+        buffer: vec![0, 1, 2, 3, 4, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let rgba = pixel_art.to_rgba8();
+    pixel_art.dedup_palette();
+    assert_eq!(
+        pixel_art.palette(),
+        &vec![0xff0000, 0x00ff00, 0x0000ff, 0x000000]
+    );
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 0, 2, 1, 0]);
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+    let mut pixel_art = PixelArt {
+        palette: vec![0x000000, 0x000000, 0x000000],
+        buffer: vec![0, 1, 2, 1],
+        size: [2, 2],
+        transparent: Some(1),
+        alpha: None,
+    };
+    pixel_art.dedup_palette();
+    assert_eq!(pixel_art.palette(), &vec![0x000000, 0x000000]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 0, 1]);
+    assert_eq!(pixel_art.transparent_index(), Some(1));
+}
+
+#[test]
+fn crop() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff, 0xffffff],
+        buffer: vec![0, 1, 2, 3, 3, 2, 1, 0, 0, 3, 3, 0],
+        size: [4, 3],
+        transparent: Some(3),
+        alpha: None,
+    };
+    let cropped = pixel_art.crop(1, 1, 3, 2).unwrap();
+    assert_eq!(cropped.dimensions(), [3, 2]);
+    assert_eq!(
+        cropped.palette(),
+        &vec![0x0000ff, 0x00ff00, 0xff0000, 0xffffff]
+    );
+    assert_eq!(cropped.buffer(), &vec![0, 1, 2, 3, 3, 2]);
+    assert_eq!(cropped.transparent_index(), Some(3));
+    let cropped = pixel_art.crop(0, 0, 2, 1).unwrap();
+    assert_eq!(cropped.palette(), &vec![0xff0000, 0x00ff00]);
+    assert_eq!(cropped.transparent_index(), None);
+    assert_eq!(pixel_art.crop(0, 0, 4, 3).unwrap(), pixel_art);
+    assert!(matches!(
+        pixel_art.crop(2, 0, 3, 1),
+        Err(Error::RegionOutOfRange { x: 2, .. })
+    ));
+    assert!(matches!(
+        pixel_art.crop(0, 1, u32::MAX, 1),
+        Err(Error::RegionOutOfRange { .. })
+    ));
+    assert!(matches!(pixel_art.crop(0, 0, 0, 1), Err(Error::EmptyImage)));
+}
+
+#[test]
+fn resize_nearest() {
+    let original = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 1, 0],
+        size: [3, 2],
+        transparent: Some(2),
+        alpha: None,
+    };
+    let mut pixel_art = original.clone();
+    pixel_art.resize_nearest(12, 8).unwrap();
+    assert_eq!(pixel_art.dimensions(), [12, 8]);
+    assert_eq!(pixel_art.get_pixel(7, 3), Some(0x00ff00));
+    assert_eq!(pixel_art.get_pixel(8, 3), Some(0x0000ff));
+    assert_eq!(pixel_art.get_pixel(8, 4), Some(0xff0000));
+    pixel_art.resize_nearest(3, 2).unwrap();
+    assert_eq!(pixel_art, original);
+    pixel_art.resize_nearest(1, 1).unwrap();
+    assert_eq!(pixel_art.palette(), &vec![0x00ff00]);
+    assert_eq!(pixel_art.transparent_index(), None);
+    assert!(matches!(
+        pixel_art.resize_nearest(0, 1),
+        Err(Error::EmptyImage)
+    ));
+}
+
+#[test]
+fn flip() {
+    let original = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 1, 0],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut pixel_art = original.clone();
+    pixel_art.flip_horizontal();
+    assert_eq!(pixel_art.buffer(), &vec![2, 1, 0, 0, 1, 1]);
+    assert_eq!(pixel_art.get_pixel(0, 0), Some(0x0000ff));
+    pixel_art.flip_horizontal();
+    assert_eq!(pixel_art, original);
+    pixel_art.flip_vertical();
+    assert_eq!(pixel_art.buffer(), &vec![1, 1, 0, 0, 1, 2]);
+    assert_eq!(pixel_art.palette(), original.palette());
+    pixel_art.flip_vertical();
+    assert_eq!(pixel_art, original);
+}
+
+#[test]
+fn rotate() {
+    let original = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 1, 0],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut pixel_art = original.clone();
+    pixel_art.rotate(1);
+    assert_eq!(pixel_art.dimensions(), [2, 3]);
+    assert_eq!(pixel_art.buffer(), &vec![1, 0, 1, 1, 0, 2]);
+    pixel_art.rotate(1);
+    let mut half_turn = original.clone();
+    half_turn.rotate(6);
+    assert_eq!(pixel_art, half_turn);
+    assert_eq!(half_turn.buffer(), &vec![0, 1, 1, 2, 1, 0]);
+    pixel_art.rotate(3);
+    pixel_art.rotate(3);
+    assert_eq!(pixel_art, original);
+    pixel_art.rotate(4);
+    assert_eq!(pixel_art, original);
+}
+
+#[test]
+fn transparency() {
+    let rgba = [0xff, 0, 0, 0xff, 1, 2, 3, 0, 9, 9, 9, 0, 0, 0, 0xff, 0xff];
+    let opaque = PixelArt::from_rgba8([2, 2], &rgba, ImageOptions::default());
+    assert_eq!(opaque.palette().len(), 4);
+    assert_eq!(opaque.transparent_index(), None);
+    let options = ImageOptions {
+        transparency: true,
+        ..Default::default()
+    };
+    let mut pixel_art = PixelArt::from_rgba8([2, 2], &rgba, options);
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x000000, 0x0000ff]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 1, 2]);
+    assert_eq!(pixel_art.transparent_index(), Some(1));
+    assert_eq!(pixel_art.to_rgba8()[4..12], [0, 0, 0, 0, 0, 0, 0, 0]);
+    let string = pixel_art.display(Default::default()).unwrap().to_string();
+    assert!(string.contains("bool transparent = false;"));
+    assert!(string.contains("transparent = i == 1;\n    return PALETTE[i];"));
+    assert!(string.contains("if (transparent) O.xyz = vec3(0.5);"));
+    pixel_art.swap_palette_index(0, 1).unwrap();
+    assert_eq!(pixel_art.transparent_index(), Some(0));
+    pixel_art.set_pixel(0, 0, 0x000000).unwrap();
+    assert_eq!(pixel_art.palette().len(), 4);
+    assert_eq!(pixel_art.transparent_index(), Some(0));
+}
+
+#[test]
+fn alpha_threshold() {
+    let rgba = [
+        0xff, 0, 0, 0x80, 0xff, 0, 0, 0x7f, 0, 0, 0xff, 0x01, 0, 0, 0xff, 0xff,
+    ];
+    let options = ImageOptions {
+        transparency: true,
+        alpha_threshold: 0x80,
+        ..Default::default()
+    };
+    let pixel_art = PixelArt::from_rgba8([2, 2], &rgba, options);
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x000000, 0x0000ff]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 1, 2]);
+    assert_eq!(pixel_art.transparent_index(), Some(1));
+    let options = ImageOptions {
+        alpha_threshold: 0,
+        ..options
+    };
+    let pixel_art = PixelArt::from_rgba8([2, 2], &rgba, options);
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x0000ff]);
+    assert_eq!(pixel_art.transparent_index(), None);
+}
+
+#[test]
+fn alpha_buffer() {
+    let rgba = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x80, 0xff, 0, 0, 0, 0xff, 0, 0, 0xff,
+    ];
+    let options = ImageOptions {
+        alpha_bits: Some(2),
+        ..Default::default()
+    };
+    let mut pixel_art = PixelArt::from_rgba8([2, 2], &rgba, options);
+    assert_eq!(pixel_art.palette(), &vec![0xffffff, 0xff0000]);
+    assert_eq!(pixel_art.alpha().unwrap().levels(), &vec![3, 2, 0, 3]);
+    assert_eq!(pixel_art.to_rgba8()[4..8], [0xff, 0xff, 0xff, 0xaa]);
+    let string = pixel_art.display(Default::default()).unwrap().to_string();
+    // the bottom row first, the first level in the least significant bits
+    assert!(string.contains("const int ALPHA_BITS = 2;\nconst int ALPHA[] = int[](\n    188\n);"));
+    assert!(string.contains("float getAlpha(in ivec2 u) {\n    int idx = u.y * WIDTH + u.x;\n"));
+    assert!(string.contains("? mix(vec3(0.5), getColor(u), getAlpha(u)) : vec3(0.5);"));
+    let config = DisplayConfig {
+        background: BackgroundMode::Discard,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("? vec4(getColor(u), getAlpha(u)) : vec4(0);"));
+    pixel_art.flip_horizontal();
+    assert_eq!(pixel_art.alpha().unwrap().levels(), &vec![2, 3, 3, 0]);
+    let cropped = pixel_art.crop(1, 0, 1, 2).unwrap();
+    assert_eq!(cropped.alpha().unwrap().levels(), &vec![3, 0]);
+    pixel_art.alpha.as_mut().unwrap().levels[0] = 4;
+    assert!(matches!(
+        pixel_art.validate(),
+        Err(Error::AlphaLevelOutOfRange { level: 4, bits: 2 })
+    ));
+    let options = ImageOptions {
+        alpha_bits: Some(3),
+        ..Default::default()
+    };
+    assert!(matches!(
+        options.validate(),
+        Err(Error::InvalidAlphaBits(3))
+    ));
+}
+
+#[test]
+fn rgba_palette() {
+    let rgba = [
+        0xff, 0, 0, 0xff, 0xff, 0, 0, 0x80, 0xff, 0, 0, 0x80, 0xff, 0xff, 0xff, 0xff,
+    ];
+    let options = ImageOptions {
+        palette_alpha: true,
+        ..Default::default()
+    };
+    let pixel_art = PixelArt::from_rgba8([2, 2], &rgba, options);
+    assert_eq!(pixel_art.palette(), &vec![0xff0000, 0x7fff0000, 0xffffff]);
+    assert_eq!(pixel_art.to_rgba8(), rgba);
+    let mut config = DisplayConfig {
+        palette_format: PaletteFormat::RGBAFloat,
+        background: BackgroundMode::Discard,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("const vec4 PALETTE[] = vec4[](\n    vec4(1, 0, 0, 1),\n    vec4(1, 0, 0, 0.502),\n    vec4(1)\n);"));
+    assert!(string.contains("vec4 getColor(in ivec2 u) {"));
+    assert!(
+        string.contains("O = u == abs(u) && u.x < WIDTH && u.y < HEIGHT ? getColor(u) : vec4(0);")
+    );
+    config.background = BackgroundMode::Gray;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("? mix(vec3(0.5), getColor(u).xyz, getColor(u).w) : vec3(0.5);"));
+    // without the alpha in the palette, the colors are opaque
+    let opaque = PixelArt::from_rgba8([2, 2], &rgba, Default::default());
+    assert_eq!(opaque.palette(), &vec![0xff0000, 0xffffff]);
+    config.palette_format = PaletteFormat::RGBADecimal;
+    let string = opaque.display(config).unwrap().to_string();
+    assert!(string.contains("vec4(255, 0, 0, 255) / 255.0,"));
+}
+
+#[test]
+fn packed_argb() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x7f0000ff],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        palette_format: PaletteFormat::PackedARGB,
+        background: BackgroundMode::Discard,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(
+        string.contains("const uint PALETTE[] = uint[](\n    0xffff0000U,\n    0x800000ffU\n);")
+    );
+    assert!(string.contains("vec4 unpackARGB(uint color) {"));
+    assert!(string.contains("uint getColor(in ivec2 u) {"));
+    assert!(string.contains("? unpackARGB(getColor(u)) : vec4(0);"));
+    config.background = BackgroundMode::Gray;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(
+        "? mix(vec3(0.5), unpackARGB(getColor(u)).xyz, unpackARGB(getColor(u)).w) : vec3(0.5);"
+    ));
+    config.vec4_get_color = true;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("vec4 getColor(in ivec2 u) {"));
+    assert!(string.contains("return unpackARGB(PALETTE["));
+    assert!(string.contains("? mix(vec3(0.5), getColor(u).xyz, getColor(u).w) : vec3(0.5);"));
+}
+
+#[test]
+fn premultiplied_alpha() {
+    // white of 50% alpha, whose level 128 of 8 bits is `128.0 / 255.0`
+    let rgba = [0xff, 0xff, 0xff, 0x80];
+    let options = ImageOptions {
+        alpha_bits: Some(8),
+        ..Default::default()
+    };
+    let pixel_art = PixelArt::from_rgba8([1, 1], &rgba, options);
+    assert_eq!(pixel_art.alpha().unwrap().levels(), &vec![128]);
+    let mut config = DisplayConfig {
+        background: BackgroundMode::Discard,
+        premultiplied_alpha: true,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("O = u == abs(u) && u.x < WIDTH && u.y < HEIGHT ? vec4(getColor(u), getAlpha(u)) : vec4(0);\n    O.xyz *= O.w;\n"));
+    config.background = BackgroundMode::Repeat;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("    O = vec4(getColor(u), getAlpha(u));\n    O.xyz *= O.w;\n"));
+    // opaque output
+    config.background = BackgroundMode::Gray;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(!string.contains("O.xyz *= O.w;"));
+    // no alpha to multiply
+    let opaque = PixelArt::from_rgba8([1, 1], &rgba, Default::default());
+    config.background = BackgroundMode::Discard;
+    let string = opaque.display(config).unwrap().to_string();
+    assert!(!string.contains("O.xyz *= O.w;"));
+}
+
+#[test]
+fn swap_rb() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff8000, 0x123456],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    pixel_art.swap_rb();
+    assert_eq!(pixel_art.palette(), &vec![0x0080ff, 0x563412]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 1, 1, 0]);
+    assert_eq!(
+        &pixel_art.to_rgba8()[..8],
+        &[0x00, 0x80, 0xff, 0xff, 0x56, 0x34, 0x12, 0xff]
+    );
+    pixel_art.swap_rb();
+    assert_eq!(pixel_art.palette(), &vec![0xff8000, 0x123456]);
+    // the alpha in the palette is kept
+    pixel_art.palette[1] = 0x7f123456;
+    pixel_art.swap_rb();
+    assert_eq!(pixel_art.palette(), &vec![0x0080ff, 0x7f563412]);
+}
+
+#[test]
+fn bit_width_override() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 1, 2, 1, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    config.buffer_format.bit_width = Some(8);
+    let display = pixel_art.display(config).unwrap();
+    assert_eq!(display.bit_shift(), 8);
+    assert!(display.to_string().contains("CHUNKS_IN_U32 = 4;"));
+    config.buffer_format.bit_width = Some(3);
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::InvalidBitWidth(3))
+    ));
+    config.buffer_format.bit_width = Some(1);
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::PaletteNotFitBitWidth { .. })
+    ));
+}
+
+#[test]
+fn tight_packing() {
+    let pixel_art = PixelArt {
+        palette: vec![0, 1, 2, 3, 4, 5],
+        buffer: (0..24).map(|i| i % 6).collect(),
+        size: [12, 2],
+        transparent: None,
+        alpha: None,
+    };
+    assert_eq!(pixel_art.necessary_bit_width(), 3);
+    let mut config = DisplayConfig::default();
+    config.buffer_format.reverse_rows = false;
+    config.buffer_format.tight_packing = true;
+    let display = pixel_art.display(config).unwrap();
+    let (stream, _) = display.compressed_buffer();
+    assert_eq!(stream.len(), 3);
+    let unpack = |i: usize| {
+        let (word, offset) = (i * 3 / 32, i * 3 % 32);
+        let mut chunk = stream[word] >> offset;
+        if offset + 3 > 32 {
+            chunk |= stream[word + 1] << (32 - offset);
+        }
+        chunk & 7
+    };
+    assert!((0..24).all(|i| unpack(i) == pixel_art.buffer[i]));
+    assert!(display.to_string().contains("BIT_WIDTH = 3;"));
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn force_uint() {
+    let pixel_art = PixelArt {
+        palette: vec![0, 1],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    let display = pixel_art.display(config).unwrap();
+    assert!(display.stats().intable);
+    assert!(display.to_string().contains("const int BUFFER[]"));
+    config.buffer_format.force_uint = true;
+    let display = pixel_art.display(config).unwrap();
+    assert!(!display.stats().intable);
+    assert!(display
+        .to_string()
+        .contains("const uint BUFFER[] = uint[](\n    9U\n);"));
+}
+
+#[test]
+fn pack_uvec2() {
+    let pixel_art = PixelArt {
+        palette: vec![0, 1, 2, 3],
+        buffer: (0..48).map(|i| i % 4).collect(),
+        size: [24, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    let (words, _) = pixel_art.display(config).unwrap().compressed_buffer();
+    config.buffer_format.pack_uvec2 = true;
+    let display = pixel_art.display(config).unwrap();
+    let (buffer, intable) = display.compressed_buffer();
+    assert_eq!(words.len(), 3);
+    assert_eq!(&buffer[..3], &words[..]);
+    assert_eq!(buffer[3], 0);
+    assert!(!intable);
+    assert_eq!(display.stats().buffer_len, 2);
+    let string = display.to_string();
+    assert!(string.contains("const uvec2 BUFFER[] = uvec2[](\n    uvec2("));
+    assert!(string.contains("BUFFER[u.y / 2][u.y % 2] >>"));
+    config.buffer_format.pack_uvec4 = true;
+    let display = pixel_art.display(config).unwrap();
+    let (buffer, _) = display.compressed_buffer();
+    assert_eq!(&buffer[..3], &words[..]);
+    assert_eq!(buffer.len(), 4);
+    assert_eq!(display.stats().buffer_len, 1);
+    let string = display.to_string();
+    assert!(string.contains(&format!(
+        "const uvec4 BUFFER[] = uvec4[](\n    uvec4({}U, {}U, {}U, 0U)\n);",
+        words[0], words[1], words[2]
+    )));
+    assert!(string.contains("BUFFER[u.y / 4][u.y % 4] >>"));
+}
+
+#[test]
+fn palette_limit() {
+    let pixel_art = PixelArt {
+        palette: (0..17).collect(),
+        buffer: (0..17).collect(),
+        size: [17, 1],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        palette_limit: Some(16),
+        ..Default::default()
+    };
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::PaletteLengthOver {
+            limit: 16,
+            palette_len: 17
+        })
+    ));
+    config.palette_limit = Some(17);
+    assert!(pixel_art.display(config).is_ok());
+}
+
+#[test]
+fn estimated_len() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let geekest = DisplayConfig {
+        inline_level: InlineLevel::Geekest,
+        palette_format: PaletteFormat::RGBFloat,
+        ..Default::default()
+    };
+    [DisplayConfig::default(), geekest]
+        .into_iter()
+        .for_each(|config| {
+            let display = pixel_art.display(config).unwrap();
+            assert_eq!(display.estimated_len(), display.to_string().len());
+        });
+}
+
+#[test]
+fn write_to() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let display = pixel_art.display(DisplayConfig::default()).unwrap();
+    let mut vec = Vec::new();
+    display.write_to(&mut vec).unwrap();
+    assert_eq!(String::from_utf8(vec).unwrap(), display.to_string());
+}
+
+#[test]
+fn column_major() {
+    let pixel_art = PixelArt {
+        palette: vec![0x000000, 0xffffff, 0xff0000],
+        buffer: vec![0, 1, 2, 2, 1, 0],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        buffer_format: BufferFormat {
+            force_to_raw: true,
+            column_major: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let display = pixel_art.display(config).unwrap();
+    assert_eq!(display.compressed_buffer().0, vec![2, 0, 1, 1, 0, 2]);
+    assert!(display
+        .to_string()
+        .contains("int idx = u.x * HEIGHT + u.y;"));
+    config.buffer_format.reverse_rows = false;
+    config.inline_level = InlineLevel::InlineVariable;
+    let display = pixel_art.display(config).unwrap();
+    assert_eq!(display.compressed_buffer().0, vec![0, 2, 1, 1, 2, 0]);
+    assert!(display.to_string().contains("int idx = u.x * 2 + 1 - u.y;"));
+}
+
+#[test]
+fn two_dimensional() {
+    let pixel_art = PixelArt {
+        palette: vec![0x000000, 0xffffff, 0xff0000],
+        buffer: vec![0, 1, 2, 2, 1, 0],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        buffer_format: BufferFormat {
+            force_to_raw: true,
+            two_dimensional: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(
+        "const int BUFFER[HEIGHT][WIDTH] = int[HEIGHT][WIDTH](
+    int[WIDTH](2, 1, 0),
+    int[WIDTH](0, 1, 2)
+);"
+    ));
+    assert!(string.contains("    return PALETTE[BUFFER[u.y][u.x]];\n}"));
+    config.buffer_format.column_major = true;
+    config.buffer_format.reverse_rows = false;
+    config.inline_level = InlineLevel::InlineVariable;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("int[3][2](\n    int[2](0, 2),"));
+    assert!(string.contains("BUFFER[u.x][1 - u.y]"));
+    config.buffer_format.force_to_raw = false;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("BUFFER[] = "));
+}
+
+#[test]
+fn run_length() {
+    let pixel_art = PixelArt {
+        palette: vec![0x000000, 0xffffff],
+        buffer: (0..128).map(|i| (i >= 80) as u32).collect(),
+        size: [16, 8],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    config.buffer_format.encoding = BufferEncoding::RunLength;
+    let display = pixel_art.display(config).unwrap();
+    assert_eq!(
+        display.compressed_buffer(),
+        (vec![48 << 16 | 1, 80 << 16], true)
+    );
+    let string = display.to_string();
+    assert!(string.contains("const int BUFFER[] = int[](\n    3145729, 5242880\n);"));
+    assert!(string.contains("} while (end <= idx);\n    return PALETTE[run & 0xffff];"));
+    config.buffer_format.encoding = BufferEncoding::Smallest;
+    assert_eq!(pixel_art.display(config).unwrap().to_string(), string);
+    let random = PixelArt {
+        palette: vec![0x000000, 0xffffff],
+        buffer: (0..128).map(|i| i % 2).collect(),
+        size: [16, 8],
+        transparent: None,
+        alpha: None,
+    };
+    let packed = random
+        .display(DisplayConfig::default())
+        .unwrap()
+        .to_string();
+    assert_eq!(random.display(config).unwrap().to_string(), packed);
+}
+
+#[test]
+fn hex_literals() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2, 2, 2],
+        size: [4, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::builder().hex_literals(true).build().unwrap();
+    // the bottom row `0, 2, 2, 2` then the top row `0, 1, 2, 1`, two bits each from the least significant
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("const int BUFFER[] = int[](\n    0x64a8\n);"));
+    // the palette is not affected
+    assert!(string.contains("vec3(255, 0, 0) / 255.0"));
+    config.buffer_format.force_uint = true;
+    config.uppercase_hex = true;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("const uint BUFFER[] = uint[](\n    0x64A8U\n);"));
+    config.buffer_format.pack_uvec2 = true;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("uvec2(0x64A8U, 0x0U)"));
+    // also in the other targets
+    config.buffer_format.pack_uvec2 = false;
+    config.target = Target::C;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("static const uint32_t buffer[] = {\n    0x64A8U,\n};"));
+    config.target = Target::Rust;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("pub const BUFFER: [u32; 1] = [\n    0x64A8,\n];"));
+}
+
+#[test]
+fn best_buffer_format() {
+    let base = BufferFormat {
+        reverse_rows: false,
+        ..Default::default()
+    };
+    let flat = PixelArt {
+        palette: vec![0x000000, 0xffffff],
+        buffer: [vec![0; 100], vec![1; 100]].concat(),
+        size: [20, 10],
+        transparent: None,
+        alpha: None,
+    };
+    let best = flat.best_buffer_format(base);
+    assert_eq!(best.encoding, BufferEncoding::RunLength);
+    assert!(!best.reverse_rows);
+    let random = PixelArt {
+        palette: (0..5).collect(),
+        buffer: (0..256).map(|i| i * 7 % 5).collect(),
+        size: [16, 16],
+        transparent: None,
+        alpha: None,
+    };
+    let best = random.best_buffer_format(base);
+    assert!(best.tight_packing);
+    assert_eq!(best.encoding, BufferEncoding::Packed);
+    // ties go to the power of two packing
+    let four = PixelArt {
+        palette: (0..4).collect(),
+        ..random
+    };
+    assert_eq!(four.best_buffer_format(base), base);
+}
+
+#[test]
+fn encode_code_and_stats() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let config = DisplayConfig::default();
+    let display = pixel_art.display(config).unwrap();
+    let (code, stats) = encode(&pixel_art, &config).unwrap();
+    assert_eq!(code, display.to_string());
+    assert_eq!(stats, display.stats());
+    let geekest = DisplayConfig {
+        inline_level: InlineLevel::Geekest,
+        ..config
+    };
+    assert!(matches!(
+        encode(&pixel_art, &geekest),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn palette_len_constant() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        palette_len_constant: true,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(
+        string.contains("const int WIDTH = 3, HEIGHT = 2, PALETTE_LEN = 3, CHUNKS_IN_U32 = 16;\n")
+    );
+    config.inline_level = InlineLevel::InlineVariable;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(!string.contains("PALETTE_LEN"));
+}
+
+#[test]
+fn bounds_check() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        bounds_check: BoundsCheck::Clamp,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(
+        "vec3 getColor(in ivec2 u) {\n    u = clamp(u, ivec2(0), ivec2(WIDTH, HEIGHT) - 1);\n"
+    ));
+    config.bounds_check = BoundsCheck::Background;
+    config.inline_level = InlineLevel::InlineVariable;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(
+        "    if (u != abs(u) || u.x >= 3 || u.y >= 2) { return vec3(128, 128, 128) / 255.0; }\n"
+    ));
+    config.palette_format = PaletteFormat::IntegerHexadecimal;
+    config.background = BackgroundMode::Color(0x123456);
+    config.vec4_get_color = true;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("{ return vec4(int2rgb(0x123456), 1.0); }"));
+}
+
+#[test]
+fn format_style() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let config = DisplayConfig {
+        format_style: FormatStyle::Compact,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.starts_with(
+        "const vec3 PALETTE[] = vec3[](vec3(255,0,0)/255.,vec3(0,255,0)/255.,vec3(0,0,255)/255.);
+const int WIDTH = 3, HEIGHT = 2, CHUNKS_IN_U32 = 16;
+const int BUFFER[] = int[](2337);
+vec3 getColor(in ivec2 u) {
+int idx = u.y * WIDTH + u.x;
+"
+    ));
+    let inline = DisplayConfig {
+        inline_level: InlineLevel::InlineVariable,
+        ..config
+    };
+    let string = pixel_art.display(inline).unwrap().to_string();
+    assert!(
+        string.contains("int[](2337);\nvec3 getColor(in ivec2 u) {\nint idx = u.y * 3 + u.x;\n")
+    );
+}
+
+#[test]
+fn compression_stats() {
+    let pixel_art = PixelArt {
+        palette: vec![0, 1, 2, 3, 4, 5],
+        buffer: (0..24).map(|i| i % 6).collect(),
+        size: [12, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    let stats = pixel_art.display(config).unwrap().stats();
+    assert_eq!(stats.buffer_len, 3);
+    assert_eq!(stats.byte_size, 12);
+    assert_eq!(stats.bits_per_pixel, 4.0);
+    assert_eq!(stats.bit_shift, Some(4));
+    config.buffer_format.force_to_raw = true;
+    let stats = pixel_art.display(config).unwrap().stats();
+    assert_eq!(stats.buffer_len, 24);
+    assert_eq!(stats.bit_shift, None);
+    assert!(stats.intable);
+}
+
+#[test]
+fn degenerate_image() {
+    let empty = PixelArt {
+        palette: Vec::new(),
+        buffer: Vec::new(),
+        size: [0, 0],
+        transparent: None,
+        alpha: None,
+    };
+    assert_eq!(empty.necessary_bit_shift(), 1);
+    assert!(matches!(
+        empty.display(Default::default()),
+        Err(Error::EmptyImage)
+    ));
+    let single = PixelArt {
+        palette: vec![0xffffff],
+        buffer: vec![0],
+        size: [1, 1],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    [InlineLevel::None, InlineLevel::InlineVariable]
+        .iter()
+        .for_each(|inline_level| {
+            config.inline_level = *inline_level;
+            assert!(single
+                .display(config)
+                .unwrap()
+                .to_string()
+                .contains("BUFFER[]"));
+        });
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(single
+        .display(config)
+        .unwrap()
+        .to_string()
+        .contains("vec3(1)"));
+}
+
 #[derive(Clone, Copy, Debug)]
 struct ColorDisplay {
     format: PaletteFormat,
     space_delim: &'static str,
+    uppercase_hex: bool,
+    normalization: Normalization,
     color: u32,
 }
 impl std::fmt::Display for ColorDisplay {
@@ -220,50 +3333,78 @@ impl std::fmt::Display for ColorDisplay {
             true => "",
             false => "0",
         };
+        let hex = |x: u32| match self.uppercase_hex {
+            true => format!("{x:#X}"),
+            false => format!("{x:#x}"),
+        };
+        let normalize = match self.normalization {
+            Normalization::Divide => format!("{space}/{space}255.{zero}"),
+            Normalization::Multiply => {
+                format!("{space}*{space}(1.{zero}{space}/{space}255.{zero})")
+            }
+        };
+        // `0.690` or `.69` without spaces
+        let unit_float = |x: u32| {
+            let unit = match space.is_empty() {
+                true => 100.0,
+                false => 1000.0,
+            };
+            let x = (f32::round(x as f32 / 255.0 * unit) / unit).to_string();
+            match x.len() > 1 && space.is_empty() {
+                true => x[1..].to_string(),
+                false => x,
+            }
+        };
+        let [transparency, r, g, b] = self.color.to_be_bytes().map(|x| x as u32);
+        let alpha = 0xff - transparency;
         match self.format {
-            PaletteFormat::IntegerDecimal => f.write_fmt(format_args!("{}", self.color)),
-            PaletteFormat::IntegerHexadecimal => f.write_fmt(format_args!("{:#x}", self.color)),
+            PaletteFormat::IntegerDecimal => f.write_fmt(format_args!("{}", self.color & 0xffffff)),
+            PaletteFormat::IntegerHexadecimal => f.write_str(&hex(self.color & 0xffffff)),
             PaletteFormat::RGBDecimal => f.write_fmt(format_args!(
-                "vec3({},{space}{},{space}{}){space}/{space}255.{zero}",
+                "vec3({},{space}{},{space}{}){normalize}",
                 (self.color & 0xFF0000) >> 16,
                 (self.color & 0x00FF00) >> 8,
                 self.color & 0x0000FF
             )),
             PaletteFormat::RGBHexadecimal => f.write_fmt(format_args!(
-                "vec3({:#x},{space}{:#x},{space}{:#x}){space}/{space}255.{zero}",
-                (self.color & 0xFF0000) >> 16,
-                (self.color & 0x00FF00) >> 8,
-                self.color & 0x0000FF
+                "vec3({},{space}{},{space}{}){normalize}",
+                hex((self.color & 0xFF0000) >> 16),
+                hex((self.color & 0x00FF00) >> 8),
+                hex(self.color & 0x0000FF)
             )),
             PaletteFormat::RGBFloat => {
-                let unit = match space.is_empty() {
-                    true => 100.0,
-                    false => 1000.0,
-                };
-                let r = (f32::round(((self.color & 0xFF0000) >> 16) as f32 / 255.0 * unit) / unit)
-                    .to_string();
-                let r = match r.len() > 1 && space.is_empty() {
-                    true => &r[1..],
-                    false => &r[0..],
-                };
-                let g = (f32::round(((self.color & 0x00FF00) >> 8) as f32 / 255.0 * unit) / unit)
-                    .to_string();
-                let g = match g.len() > 1 && space.is_empty() {
-                    true => &g[1..],
-                    false => &g[0..],
-                };
-                let b =
-                    (f32::round((self.color & 0x0000FF) as f32 / 255.0 * unit) / unit).to_string();
-                let b = match b.len() > 1 && space.is_empty() {
-                    true => &b[1..],
-                    false => &b[0..],
-                };
+                let [r, g, b] = [r, g, b].map(unit_float);
                 if r == g && g == b {
                     f.write_fmt(format_args!("vec3({r})"))
                 } else {
                     f.write_fmt(format_args!("vec3({r},{space}{g},{space}{b})",))
                 }
             }
+            PaletteFormat::PackedARGB => {
+                let argb = alpha << 24 | self.color & 0xffffff;
+                let digits = match self.uppercase_hex {
+                    true => format!("{argb:08X}"),
+                    false => format!("{argb:08x}"),
+                };
+                f.write_fmt(format_args!("0x{digits}U"))
+            }
+            PaletteFormat::RGBADecimal => f.write_fmt(format_args!(
+                "vec4({r},{space}{g},{space}{b},{space}{alpha}){normalize}"
+            )),
+            PaletteFormat::RGBAFloat => {
+                let [r, g, b, a] = [r, g, b, alpha].map(unit_float);
+                if r == g && g == b && b == a {
+                    f.write_fmt(format_args!("vec4({r})"))
+                } else {
+                    f.write_fmt(format_args!("vec4({r},{space}{g},{space}{b},{space}{a})"))
+                }
+            }
+            PaletteFormat::Grayscale => {
+                let [_, r, g, b] = self.color.to_be_bytes();
+                let luminance = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+                let luminance = f32::round(luminance / 255.0 * 1000.0) / 1000.0;
+                f.write_fmt(format_args!("{luminance:?}"))
+            }
         }
     }
 }
@@ -273,6 +3414,8 @@ fn palette_format() {
     let mut display = ColorDisplay {
         format: PaletteFormat::IntegerDecimal,
         space_delim: " ",
+        uppercase_hex: false,
+        normalization: Normalization::Divide,
         color: 11596387,
     };
     assert_eq!("11596387", &display.to_string());
@@ -280,45 +3423,328 @@ fn palette_format() {
     assert_eq!("0xb0f263", &display.to_string());
     display.format = PaletteFormat::RGBDecimal;
     assert_eq!("vec3(176, 242, 99) / 255.0", &display.to_string());
+    display.normalization = Normalization::Multiply;
+    assert_eq!("vec3(176, 242, 99) * (1.0 / 255.0)", &display.to_string());
+    display.space_delim = "";
+    assert_eq!("vec3(176,242,99)*(1./255.)", &display.to_string());
+    display.space_delim = " ";
+    display.normalization = Normalization::Divide;
     display.format = PaletteFormat::RGBHexadecimal;
     assert_eq!("vec3(0xb0, 0xf2, 0x63) / 255.0", &display.to_string());
+    display.uppercase_hex = true;
+    assert_eq!("vec3(0xB0, 0xF2, 0x63) / 255.0", &display.to_string());
+    display.format = PaletteFormat::IntegerHexadecimal;
+    assert_eq!("0xB0F263", &display.to_string());
+    display.uppercase_hex = false;
     display.format = PaletteFormat::RGBFloat;
     assert_eq!("vec3(0.69, 0.949, 0.388)", &display.to_string());
+    display.format = PaletteFormat::RGBAFloat;
+    assert_eq!("vec4(0.69, 0.949, 0.388, 1)", &display.to_string());
+    display.color = 0x7fb0f263;
+    assert_eq!("vec4(0.69, 0.949, 0.388, 0.502)", &display.to_string());
+    display.format = PaletteFormat::RGBADecimal;
+    assert_eq!("vec4(176, 242, 99, 128) / 255.0", &display.to_string());
+    display.format = PaletteFormat::PackedARGB;
+    assert_eq!("0x80b0f263U", &display.to_string());
+    display.color = 0xb0f263;
+    assert_eq!("0xffb0f263U", &display.to_string());
+    display.color = 0x7fb0f263;
+    display.format = PaletteFormat::IntegerDecimal;
+    assert_eq!("11596387", &display.to_string());
+    display.color = 11596387;
+    display.format = PaletteFormat::Grayscale;
+    assert_eq!("0.808", &display.to_string());
+    display.color = 0xffffff;
+    assert_eq!("1.0", &display.to_string());
+}
+
+#[test]
+fn grayscale() {
+    let pixel_art = PixelArt {
+        palette: vec![0x000000, 0x808080, 0xffffff],
+        buffer: vec![0, 1, 2, 1],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let config = DisplayConfig {
+        palette_format: PaletteFormat::Grayscale,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("const float PALETTE[] = float[](\n    0.0,\n    0.502,\n    1.0\n);"));
+    assert!(string.contains("float getColor(in ivec2 u)"));
+    assert!(string.contains("? vec3(getColor(u)) :"));
+}
+
+#[test]
+fn palette_as_defines() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        palette_format: PaletteFormat::IntegerHexadecimal,
+        inline_level: InlineLevel::InlineVariable,
+        palette_as_defines: true,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.starts_with("#define C0 0xff0000\n#define C1 0xff00\n#define C2 0xff\n\n"));
+    assert!(string.contains("return i == 0 ? C0 : i == 1 ? C1 : C2;\n"));
+    assert!(!string.contains("PALETTE"));
+    let single = PixelArt {
+        palette: vec![0xffffff],
+        buffer: vec![0],
+        size: [1, 1],
+        transparent: None,
+        alpha: None,
+    };
+    let string = single.display(config).unwrap().to_string();
+    assert!(string.contains("    return C0;\n"));
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn standalone_template() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x0000ff],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    let shadertoy = pixel_art.display(config).unwrap().to_string();
+    config.template = Template::Standalone;
+    let standalone = pixel_art.display(config).unwrap().to_string();
+    assert!(standalone.starts_with("#version 300 es\n"));
+    assert!(standalone.contains("void main() {"));
+    assert!(standalone.ends_with(&shadertoy));
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn glsl_version() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x0000ff],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig::default();
+    let shadertoy = pixel_art.display(config).unwrap().to_string();
+    config.glsl_version = Some(GlslVersion::Es310);
+    config.precision = Precision::Mediump;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert_eq!(
+        string,
+        format!("#version 310 es\nprecision mediump float;\n\n{shadertoy}")
+    );
+    config.template = Template::Standalone;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.starts_with("#version 310 es\nprecision mediump float;\nuniform vec2"));
+    assert_eq!("330".parse::<GlslVersion>().unwrap(), GlslVersion::Core330);
+    assert!(matches!(
+        "100".parse::<GlslVersion>(),
+        Err(Error::UnknownGlslVersion(_))
+    ));
+}
+
+#[test]
+fn twigl_mode() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x0000ff],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        inline_level: InlineLevel::Geekest,
+        palette_format: PaletteFormat::RGBFloat,
+        ..Default::default()
+    };
+    let geekest = pixel_art.display(config).unwrap().to_string();
+    assert!(geekest.starts_with("ivec2 u=ivec2(floor((FC.xy-.5*r)/r.y*2.+1.));"));
+    let body = geekest.replace("FC.xy", "gl_FragCoord.xy");
+    config.twigl_mode = TwiglMode::Geek;
+    let geek = pixel_art.display(config).unwrap().to_string();
+    assert_eq!(geek, format!("void main(){{{body}o.w=1.;}}"));
+    config.twigl_mode = TwiglMode::Classic;
+    let classic = pixel_art.display(config).unwrap().to_string();
+    assert!(classic.starts_with("#version 300 es\n"));
+    assert!(classic.ends_with(&format!("{body}outColor=o;}}")));
+}
+
+#[test]
+fn geekest_raw_buffer() {
+    let pixel_art = PixelArt {
+        palette: (0..0x10000).collect(),
+        buffer: (0..0x10000).collect(),
+        size: [256, 256],
+        transparent: None,
+        alpha: None,
+    };
+    let config = DisplayConfig {
+        inline_level: InlineLevel::Geekest,
+        palette_format: PaletteFormat::RGBFloat,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.starts_with(
+        "ivec2 u=ivec2(floor((FC.xy-.5*r)/r.y*256.+128.));int i=u.y*256+u.x;o .xyz=u==abs(u)&&u.x<256&&u.y<256?"
+    ));
+    assert!(string.ends_with(")[i]]:vec3(.5);"));
+}
+
+#[test]
+fn background_mode() {
+    let pixel_art = PixelArt {
+        palette: vec![0xffffff, 0x000000],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        inline_level: InlineLevel::InlineVariable,
+        ..Default::default()
+    };
+    let inside = "u == abs(u) && u.x < 2 && u.y < 2";
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(&format!("O.xyz = {inside} ? getColor(u) : vec3(0.5);")));
+    config.background = BackgroundMode::Color(0xff0000);
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(&format!("O.xyz = {inside} ? getColor(u) : vec3(1, 0, 0);")));
+    config.background = BackgroundMode::Discard;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(&format!("O = {inside} ? vec4(getColor(u), 1) : vec4(0);")));
+    config.background = BackgroundMode::DiscardFragment;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(&format!(
+        "if (!({inside})) discard;\n    O = vec4(getColor(u), 1);"
+    )));
+    config.background = BackgroundMode::ClampToEdge;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("u = clamp(u, ivec2(0), ivec2(2, 2) - 1);\n    O.xyz = getColor(u);"));
+    config.background = BackgroundMode::Repeat;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("u = ivec2(mod(vec2(u), vec2(2, 2)));\n    O.xyz = getColor(u);"));
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn display_scale() {
+    let pixel_art = PixelArt {
+        palette: vec![0xffffff, 0x000000],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        inline_level: InlineLevel::InlineVariable,
+        scale: DisplayScale::Pixels(8),
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("ivec2 u = ivec2(floor((U - 0.5 * r) / 8.0 + vec2(1.0, 1.0)));"));
+    config.scale = DisplayScale::Percent(50);
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("ivec2(floor((U - 0.5 * r) / (r.y * 0.5) * 2.0 + vec2(1.0, 1.0)));"));
+    config.rounding = Rounding::Round;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("ivec2(round((U - 0.5 * r) / (r.y * 0.5) * 2.0 + vec2(1.0, 1.0)));"));
+    config.scale = DisplayScale::Pixels(0);
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::InvalidDisplayScale)
+    ));
+    config.scale = DisplayScale::Percent(50);
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
 }
 
 #[derive(Clone, Copy, Debug)]
 struct ArrayDisplayConfig {
     return_delim: &'static str,
+    /// after the whole array
+    statement_delim: &'static str,
     indent_delim: &'static str,
     space_delim: &'static str,
     semi_colon: &'static str,
 }
 
-impl From<InlineLevel> for ArrayDisplayConfig {
-    fn from(e: InlineLevel) -> ArrayDisplayConfig {
-        match e {
-            InlineLevel::None => ArrayDisplayConfig {
-                return_delim: "\n",
-                indent_delim: "    ",
-                space_delim: " ",
-                semi_colon: ";",
+impl From<DisplayConfig> for ArrayDisplayConfig {
+    fn from(config: DisplayConfig) -> ArrayDisplayConfig {
+        match (config.inline_level, config.format_style) {
+            (InlineLevel::Geekest, _) => ArrayDisplayConfig {
+                return_delim: "",
+                statement_delim: "",
+                indent_delim: "",
+                space_delim: "",
+                semi_colon: "",
             },
-            InlineLevel::InlineVariable => ArrayDisplayConfig {
+            (_, FormatStyle::Pretty) => ArrayDisplayConfig {
                 return_delim: "\n",
-                indent_delim: "    ",
+                statement_delim: "\n\n",
+                indent_delim: config.indent.as_str(),
                 space_delim: " ",
                 semi_colon: ";",
             },
-            InlineLevel::Geekest => ArrayDisplayConfig {
+            (_, FormatStyle::Compact) => ArrayDisplayConfig {
                 return_delim: "",
+                statement_delim: "\n",
                 indent_delim: "",
                 space_delim: "",
-                semi_colon: "",
+                semi_colon: ";",
             },
         }
     }
 }
 
+/// Packs `32 / bit_shift` values of `bit_shift` bits into each `u32`, padding the last one with `0`.
+/// The first value is in the least significant bits if `reverse_each_chunk`.
+fn pack_chunks(buffer: &[u32], bit_shift: usize, reverse_each_chunk: bool) -> Vec<u32> {
+    let chunk_size = 32 / bit_shift;
+    let closure = move |sum: u32, i: &u32| *i + (sum << bit_shift);
+    buffer
+        .chunks(chunk_size)
+        .map(|a| {
+            let mut a = a.to_vec();
+            a.resize(chunk_size, 0);
+            match reverse_each_chunk {
+                true => a.iter().rev().fold(0, closure),
+                false => a.iter().fold(0, closure),
+            }
+        })
+        .collect()
+}
+
 fn int_type(intable: bool) -> &'static str {
     match intable {
         true => "int",
@@ -333,79 +3759,284 @@ fn int_value_suffix(intable: bool) -> &'static str {
     }
 }
 
+/// `std::fmt::Write` sink which only counts the bytes
+#[derive(Clone, Copy, Debug, Default)]
+struct LenCounter(usize);
+
+impl std::fmt::Write for LenCounter {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
 impl<'a> Display<'a> {
+    /// Returns the exact length of the generated code in bytes without allocating it.
+    pub fn estimated_len(&self) -> usize {
+        let mut counter = LenCounter::default();
+        std::fmt::Write::write_fmt(&mut counter, format_args!("{self}"))
+            .expect("formatting never fails");
+        counter.0
+    }
+    /// Streams the generated code to `writer` without building the whole `String`.
+    ///
+    /// Wrap files in `std::io::BufWriter`, the code is written in many small pieces.
+    pub fn write_to<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        std::io::Write::write_fmt(writer, format_args!("{self}"))
+    }
+    /// Returns statistics of the generated buffer.
+    pub fn stats(&self) -> CompressionStats {
+        let (buffer, intable) = self.compressed_buffer();
+        let [width, height] = self.entity.size;
+        let byte_size = buffer.len() * std::mem::size_of::<u32>();
+        let buffer_len = buffer.len() / self.vector_len();
+        CompressionStats {
+            buffer_len,
+            byte_size,
+            bits_per_pixel: (byte_size * 8) as f32 / (width * height) as f32,
+            intable,
+            bit_shift: (self.is_compressible() && !self.is_run_length()).then(|| self.bit_shift()),
+        }
+    }
     fn fmt_palette_array(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let format = self.config.palette_format;
         let output_type = format.element_type();
         let ArrayDisplayConfig {
             return_delim,
+            statement_delim,
             indent_delim,
             space_delim,
             semi_colon,
-        } = self.config.inline_level.into();
+        } = self.config.into();
         f.write_fmt(format_args!("{output_type}[]({return_delim}"))?;
         self.entity
             .palette
             .iter()
-            .copied()
+            .copied()
+            .enumerate()
+            .try_for_each(|(i, color)| {
+                let display = ColorDisplay {
+                    format,
+                    space_delim,
+                    uppercase_hex: self.config.uppercase_hex,
+                    normalization: self.config.normalization,
+                    color,
+                };
+                match i + 1 != self.entity.palette.len() {
+                    true => f.write_fmt(format_args!("{indent_delim}{display},{return_delim}")),
+                    false => f.write_fmt(format_args!("{indent_delim}{display}{return_delim}")),
+                }
+            })?;
+        f.write_fmt(format_args!("){semi_colon}{statement_delim}"))
+    }
+    fn fmt_non_inline_palette(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_palette_inlined() {
+            return Ok(());
+        }
+        if self.config.palette_as_defines {
+            return self.fmt_palette_defines(f);
+        }
+        let output_type = self.config.palette_format.element_type();
+        f.write_fmt(format_args!("const {output_type} PALETTE[] = "))?;
+        self.fmt_palette_array(f)
+    }
+    #[inline]
+    fn is_palette_inlined(&self) -> bool {
+        self.config.inline_palette && self.entity.palette.len() <= INLINE_PALETTE_LIMIT
+    }
+    #[inline]
+    fn color_display(&self, color: u32) -> ColorDisplay {
+        ColorDisplay {
+            format: self.config.palette_format,
+            space_delim: ArrayDisplayConfig::from(self.config).space_delim,
+            uppercase_hex: self.config.uppercase_hex,
+            normalization: self.config.normalization,
+            color,
+        }
+    }
+    fn fmt_palette_defines(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.entity
+            .palette
+            .iter()
             .enumerate()
             .try_for_each(|(i, color)| {
-                let display = ColorDisplay {
-                    format,
-                    space_delim,
-                    color,
-                };
-                match i + 1 != self.entity.palette.len() {
-                    true => f.write_fmt(format_args!("{indent_delim}{display},{return_delim}")),
-                    false => f.write_fmt(format_args!("{indent_delim}{display}{return_delim}")),
-                }
+                let display = self.color_display(*color);
+                f.write_fmt(format_args!("#define C{i} {display}\n"))
             })?;
-        f.write_fmt(format_args!("){semi_colon}{return_delim}{return_delim}"))
+        f.write_str("\n")
     }
-    fn fmt_non_inline_palette(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let output_type = self.config.palette_format.element_type();
-        f.write_fmt(format_args!("const {output_type} PALETTE[] = "))?;
-        self.fmt_palette_array(f)
+    /// Writes the return statement of `getColor`, looking up the palette entry at `index`.
+    fn fmt_return_palette(&self, index: &str, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indent = self.indent();
+        let entry = |i: usize| match self.is_palette_inlined() {
+            true => self.color_display(self.entity.palette[i]).to_string(),
+            false => format!("C{i}"),
+        };
+        let index = match self.entity.transparent {
+            Some(transparent) => {
+                f.write_fmt(format_args!(
+                    "{indent}int i = int({index});\n{indent}transparent = i == {transparent};\n"
+                ))?;
+                "i"
+            }
+            None => index,
+        };
+        if !self.is_palette_inlined() && !self.config.palette_as_defines {
+            let color = self.return_color(&format!("PALETTE[{index}]"));
+            return f.write_fmt(format_args!("{indent}return {color};\n"));
+        }
+        let last = self.entity.palette.len() - 1;
+        let mut chain = String::new();
+        if last > 0 {
+            if index != "i" {
+                f.write_fmt(format_args!("{indent}int i = int({index});\n"))?;
+            }
+            (0..last).for_each(|i| chain += &format!("i == {i} ? {} : ", entry(i)));
+        }
+        chain += &entry(last);
+        f.write_fmt(format_args!(
+            "{indent}return {};\n",
+            self.return_color(&chain)
+        ))
+    }
+    /// `color` of the palette element converted to the return type of `getColor`
+    fn return_color(&self, color: &str) -> String {
+        match self.config.palette_format {
+            _ if !self.config.vec4_get_color => color.to_string(),
+            PaletteFormat::PackedARGB => format!("unpackARGB({color})"),
+            format if format.has_alpha() => color.to_string(),
+            format if format.is_integer() => format!("vec4(int2rgb({color}), 1.0)"),
+            PaletteFormat::Grayscale => format!("vec4(vec3({color}), 1.0)"),
+            _ => format!("vec4({color}, 1.0)"),
+        }
     }
 
+    /// the indices in the order of the buffer, shifted by the index base
     #[inline]
     fn current_row_buffer(&self) -> Vec<u32> {
+        let buffer = self.ordered_indices();
+        match self.config.index_offset() {
+            0 => buffer,
+            offset => buffer.into_iter().map(|idx| idx + offset).collect(),
+        }
+    }
+    fn ordered_indices(&self) -> Vec<u32> {
+        self.ordered_layer(&self.entity.buffer)
+    }
+    /// `layer` of one value per pixel, e.g. the alpha levels, in the order of the buffer
+    fn ordered_layer(&self, layer: &[u32]) -> Vec<u32> {
+        let [width, height] = self.entity.size.map(|x| x as usize);
+        if self.config.buffer_format.column_major {
+            let rows: Vec<usize> = match self.config.buffer_format.reverse_rows {
+                true => (0..height).rev().collect(),
+                false => (0..height).collect(),
+            };
+            return (0..width)
+                .flat_map(|x| rows.iter().map(move |y| layer[y * width + x]))
+                .collect();
+        }
         match self.config.buffer_format.reverse_rows {
-            true => self
-                .entity
-                .buffer
-                .chunks(self.entity.size[0] as usize)
-                .rev()
-                .flatten()
-                .copied()
-                .collect(),
-            false => self.entity.buffer.clone(),
+            true => layer.chunks(width).rev().flatten().copied().collect(),
+            false => layer.to_vec(),
         }
     }
     fn is_compressible(&self) -> bool {
         !self.config.buffer_format.force_to_raw && self.entity.is_compressible()
     }
+    #[inline]
+    fn indent(&self) -> &'static str {
+        ArrayDisplayConfig::from(self.config).indent_delim
+    }
+    fn is_tight_packing(&self) -> bool {
+        self.is_compressible() && !self.is_run_length() && self.config.buffer_format.tight_packing
+    }
+    /// number of the `u32`s in each element of the buffer array, 2 for `uvec2` and 4 for `uvec4`
+    fn vector_len(&self) -> usize {
+        let buffer_format = self.config.buffer_format;
+        match self.is_compressible() && !self.is_run_length() {
+            true if buffer_format.pack_uvec4 => 4,
+            true if buffer_format.pack_uvec2 => 2,
+            _ => 1,
+        }
+    }
+    fn is_two_dimensional(&self) -> bool {
+        !self.is_compressible() && self.config.buffer_format.two_dimensional
+    }
+    /// `BufferEncoding::Smallest` is already resolved in `PixelArt::display`.
+    fn is_run_length(&self) -> bool {
+        self.is_compressible() && self.config.buffer_format.encoding == BufferEncoding::RunLength
+    }
+    /// runs of the same index, each of them is `count << 16 | index`
+    fn run_length_buffer(&self) -> Vec<u32> {
+        let mut runs: Vec<u32> = Vec::new();
+        self.current_row_buffer()
+            .into_iter()
+            .for_each(|idx| match runs.last_mut() {
+                Some(run) if *run & 0xffff == idx && *run >> 16 < 0xffff => *run += 1 << 16,
+                _ => runs.push(1 << 16 | idx),
+            });
+        runs
+    }
+    fn buffer_type(&self, intable: bool) -> &'static str {
+        match self.vector_len() {
+            2 => "uvec2",
+            4 => "uvec4",
+            _ => int_type(intable),
+        }
+    }
+    /// expression of the `u32` chunk at `word` in `BUFFER`
+    fn buffer_word(&self, word: &str) -> String {
+        match (self.vector_len(), word.contains(' ')) {
+            (1, _) => format!("BUFFER[{word}]"),
+            (n, true) => format!("BUFFER[({word}) / {n}][({word}) % {n}]"),
+            (n, false) => format!("BUFFER[{word} / {n}][{word} % {n}]"),
+        }
+    }
+    /// bit shift for represent pixel, overridden by `BufferFormat::bit_width`
+    fn bit_shift(&self) -> usize {
+        let buffer_format = self.config.buffer_format;
+        match (buffer_format.bit_width, buffer_format.tight_packing) {
+            (Some(bit_width), _) => bit_width as usize,
+            (None, _) if self.config.index_offset() > 0 => {
+                // the largest index is the palette size
+                let bits = usize::BITS - self.entity.palette.len().leading_zeros();
+                match buffer_format.tight_packing {
+                    true => bits as usize,
+                    false => (bits as usize).next_power_of_two(),
+                }
+            }
+            (None, true) => self.entity.necessary_bit_width(),
+            (None, false) => self.entity.necessary_bit_shift(),
+        }
+    }
     fn compressed_buffer(&self) -> (Vec<u32>, bool) {
         let buffer = self.current_row_buffer();
-        let buffer: Vec<u32> = if self.is_compressible() {
-            let bit_shift = self.entity.necessary_bit_shift();
-            let chunk_size = 32 / bit_shift;
-            let closure = move |sum: u32, i: &u32| *i + (sum << bit_shift);
-            buffer
-                .chunks(chunk_size)
-                .map(|a| {
-                    let mut a = a.to_vec();
-                    a.resize(chunk_size, 0);
-                    match self.config.buffer_format.reverse_each_chunk {
-                        true => a.iter().rev().fold(0, closure),
-                        false => a.iter().fold(0, closure),
-                    }
-                })
-                .collect()
+        let buffer: Vec<u32> = if self.is_run_length() {
+            self.run_length_buffer()
+        } else if self.is_tight_packing() {
+            let bit_width = self.bit_shift();
+            let mut stream = vec![0; (buffer.len() * bit_width).div_ceil(32)];
+            buffer.iter().enumerate().for_each(|(i, idx)| {
+                let (word, offset) = (i * bit_width / 32, i * bit_width % 32);
+                stream[word] |= idx << offset;
+                if offset + bit_width > 32 {
+                    stream[word + 1] |= idx >> (32 - offset);
+                }
+            });
+            stream
+        } else if self.is_compressible() {
+            let reverse_each_chunk = self.config.buffer_format.reverse_each_chunk;
+            pack_chunks(&buffer, self.bit_shift(), reverse_each_chunk)
         } else {
             buffer.iter().copied().map(|x| x as u32).collect()
         };
-        let intable = buffer.iter().copied().max().unwrap() < 0x80000000;
+        let mut buffer = buffer;
+        let vector_len = self.vector_len();
+        let padding = (vector_len - buffer.len() % vector_len) % vector_len;
+        buffer.resize(buffer.len() + padding, 0);
+        let intable = !(self.config.buffer_format.force_uint || self.vector_len() > 1)
+            && buffer.iter().copied().max().unwrap_or(0) < 0x80000000;
         (buffer, intable)
     }
     fn fmt_buffer_array(
@@ -413,91 +4044,239 @@ impl<'a> Display<'a> {
         buffer: &[u32],
         intable: bool,
         f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let (element_len, format_chunk_size) = match (self.vector_len(), self.is_compressible()) {
+            (4, _) => (4, 2),
+            (2, _) => (2, 4),
+            (_, true) => (1, 8),
+            (_, false) => (1, self.entity.size[0] as usize),
+        };
+        let buffer_type = self.buffer_type(intable);
+        self.fmt_int_array(
+            buffer,
+            intable,
+            buffer_type,
+            element_len,
+            format_chunk_size,
+            f,
+        )
+    }
+    /// Writes the array of `element_type`, `element_len` `u32`s each,
+    /// with `format_chunk_size` elements per line unless `elements_per_line` is given.
+    fn fmt_int_array(
+        &self,
+        buffer: &[u32],
+        intable: bool,
+        element_type: &str,
+        element_len: usize,
+        format_chunk_size: usize,
+        f: &mut Formatter<'_>,
     ) -> std::fmt::Result {
         let ArrayDisplayConfig {
             return_delim,
+            statement_delim,
             indent_delim,
             space_delim,
             semi_colon,
-        } = self.config.inline_level.into();
-        let int_type = int_type(intable);
-        f.write_fmt(format_args!("{int_type}[]({return_delim}"))?;
-        let format_chunk_size = match self.is_compressible() {
-            true => 8,
-            false => self.entity.size[0] as usize,
+        } = self.config.into();
+        f.write_fmt(format_args!("{element_type}[]({return_delim}"))?;
+        let format_chunk_size = match self.config.elements_per_line {
+            Some(elements_per_line) => usize::max(elements_per_line, 1),
+            None => format_chunk_size,
         };
+        let elements = buffer.len() / element_len;
         buffer
-            .chunks(format_chunk_size)
+            .chunks(format_chunk_size * element_len)
             .enumerate()
             .try_for_each(|(i, x)| {
                 f.write_fmt(format_args!("{indent_delim}"))?;
-                x.iter().enumerate().try_for_each(|(j, px)| {
-                    let suffix = int_value_suffix(intable);
-                    f.write_fmt(format_args!("{px}{suffix}"))?;
-                    let row_end = j + 1 == x.len();
-                    let column_end = i == (buffer.len() - 1) / format_chunk_size;
-                    match (row_end, column_end) {
-                        (true, true) => f.write_fmt(format_args!("{return_delim}")),
-                        (true, false) => f.write_fmt(format_args!(",{return_delim}")),
-                        (false, _) => f.write_fmt(format_args!(",{space_delim}")),
-                    }
-                })?;
+                let row_len = x.len() / element_len;
+                x.chunks(element_len)
+                    .enumerate()
+                    .try_for_each(|(j, element)| {
+                        let suffix = int_value_suffix(intable);
+                        match element {
+                            [px] => {
+                                f.write_fmt(format_args!("{}{suffix}", self.int_literal(*px)))?
+                            }
+                            _ => {
+                                f.write_fmt(format_args!("uvec{element_len}("))?;
+                                element.iter().enumerate().try_for_each(|(k, px)| {
+                                    let px = self.int_literal(*px);
+                                    match k {
+                                        0 => f.write_fmt(format_args!("{px}U")),
+                                        _ => f.write_fmt(format_args!(",{space_delim}{px}U")),
+                                    }
+                                })?;
+                                f.write_str(")")?
+                            }
+                        }
+                        let row_end = j + 1 == row_len;
+                        let column_end = i == (elements - 1) / format_chunk_size;
+                        match (row_end, column_end) {
+                            (true, true) => f.write_fmt(format_args!("{return_delim}")),
+                            (true, false) => f.write_fmt(format_args!(",{return_delim}")),
+                            (false, _) => f.write_fmt(format_args!(",{space_delim}")),
+                        }
+                    })?;
                 Ok(())
             })?;
-        f.write_fmt(format_args!("){semi_colon}{return_delim}{return_delim}"))
+        f.write_fmt(format_args!("){semi_colon}{statement_delim}"))
+    }
+    /// an element of the buffer arrays without the suffix, by `BufferFormat::hex_literals`
+    fn int_literal(&self, x: u32) -> String {
+        match (
+            self.config.buffer_format.hex_literals,
+            self.config.uppercase_hex,
+        ) {
+            (false, _) => x.to_string(),
+            (true, false) => format!("{x:#x}"),
+            (true, true) => format!("{x:#X}"),
+        }
     }
     fn fmt_non_inline_buffer(&self, f: &mut Formatter<'_>) -> Result<bool, std::fmt::Error> {
         let (buffer, intable) = self.compressed_buffer();
         if self.config.inline_level == InlineLevel::None {
             let [width, height] = self.entity.size;
             f.write_fmt(format_args!("const int WIDTH = {width}, HEIGHT = {height}",))?;
-            match self.is_compressible() {
-                true => {
-                    let chunk_size = 32 / self.entity.necessary_bit_shift();
+            if self.config.palette_len_constant {
+                let palette_len = self.entity.palette.len();
+                f.write_fmt(format_args!(", PALETTE_LEN = {palette_len}"))?;
+            }
+            match (self.is_tight_packing(), self.is_compressible()) {
+                _ if self.is_run_length() => f.write_str(";\n")?,
+                (true, _) => {
+                    let bit_width = self.bit_shift();
+                    f.write_fmt(format_args!(", BIT_WIDTH = {bit_width};\n"))?
+                }
+                (false, true) => {
+                    let chunk_size = 32 / self.bit_shift();
                     f.write_fmt(format_args!(", CHUNKS_IN_U32 = {chunk_size};\n"))?
                 }
-                false => f.write_str(";\n")?,
+                (false, false) => f.write_str(";\n")?,
             }
         }
-        let int_type = int_type(intable);
-        f.write_fmt(format_args!("const {int_type} BUFFER[] = "))?;
-        self.fmt_buffer_array(&buffer, intable, f)?;
+        let buffer_type = self.buffer_type(intable);
+        if self.is_two_dimensional() {
+            let [outer, inner] = self.buffer_2d_size();
+            f.write_fmt(format_args!(
+                "const {buffer_type} BUFFER[{outer}][{inner}] = "
+            ))?;
+            self.fmt_buffer_2d_array(&buffer, intable, f)?;
+        } else {
+            f.write_fmt(format_args!("const {buffer_type} BUFFER[] = "))?;
+            self.fmt_buffer_array(&buffer, intable, f)?;
+        }
         Ok(intable)
     }
+    /// lengths of the 2D buffer, rows then columns, or columns then rows if `column_major`
+    fn buffer_2d_size(&self) -> [String; 2] {
+        let names = match self.config.inline_level {
+            InlineLevel::None => ["WIDTH".to_string(), "HEIGHT".to_string()],
+            _ => self.entity.size.map(|x| x.to_string()),
+        };
+        let [width, height] = names;
+        match self.config.buffer_format.column_major {
+            true => [width, height],
+            false => [height, width],
+        }
+    }
+    fn fmt_buffer_2d_array(
+        &self,
+        buffer: &[u32],
+        intable: bool,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let ArrayDisplayConfig {
+            return_delim,
+            statement_delim,
+            indent_delim,
+            space_delim,
+            semi_colon,
+        } = self.config.into();
+        let buffer_type = self.buffer_type(intable);
+        let suffix = int_value_suffix(intable);
+        let [outer, inner] = self.buffer_2d_size();
+        let inner_len = match self.config.buffer_format.column_major {
+            true => self.entity.size[1],
+            false => self.entity.size[0],
+        } as usize;
+        f.write_fmt(format_args!(
+            "{buffer_type}[{outer}][{inner}]({return_delim}"
+        ))?;
+        let lines = buffer.len() / inner_len;
+        buffer
+            .chunks(inner_len)
+            .enumerate()
+            .try_for_each(|(i, line)| {
+                f.write_fmt(format_args!("{indent_delim}{buffer_type}[{inner}]("))?;
+                line.iter().enumerate().try_for_each(|(j, idx)| {
+                    if j > 0 {
+                        f.write_fmt(format_args!(",{space_delim}"))?;
+                    }
+                    f.write_fmt(format_args!("{}{suffix}", self.int_literal(*idx)))
+                })?;
+                match i + 1 == lines {
+                    true => f.write_fmt(format_args!("){return_delim}")),
+                    false => f.write_fmt(format_args!("),{return_delim}")),
+                }
+            })?;
+        f.write_fmt(format_args!("){semi_colon}{statement_delim}"))
+    }
     fn fmt_get_color(&self, intable: bool, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let bit_shift = self.entity.necessary_bit_shift();
-        let same_size = self.entity.size[0] as usize == 32 / bit_shift;
+        let indent = self.indent();
+        let bit_shift = self.bit_shift();
+        let same_size = !self.is_tight_packing()
+            && !self.is_run_length()
+            && !self.config.buffer_format.column_major
+            && self.entity.size[0] as usize == 32 / bit_shift;
         let element_type = self.config.palette_format.element_type();
         let reverse_rows = self.config.buffer_format.reverse_rows;
-        f.write_fmt(format_args!("{element_type} getColor(in ivec2 u) {{\n",))?;
-        let inline_none = self.config.inline_level == InlineLevel::None;
-        let width = match inline_none {
-            true => "WIDTH".to_string(),
-            false => self.entity.size[0].to_string(),
+        if self.entity.transparent.is_some() {
+            f.write_str("bool transparent = false;\n\n")?;
+        }
+        let return_type = match self.config.vec4_get_color {
+            true => "vec4",
+            false => element_type,
         };
+        f.write_fmt(format_args!("{return_type} getColor(in ivec2 u) {{\n",))?;
+        let inline_none = self.config.inline_level == InlineLevel::None;
         let semi_height = match inline_none {
             true => "HEIGHT - 1".to_string(),
             false => (self.entity.size[1] - 1).to_string(),
         };
+        self.fmt_bounds_check(f)?;
+        if self.is_two_dimensional() {
+            let y = match reverse_rows {
+                true => "u.y".to_string(),
+                false => format!("{semi_height} - u.y"),
+            };
+            let index = match self.config.buffer_format.column_major {
+                true => format!("BUFFER[u.x][{y}]"),
+                false => format!("BUFFER[{y}][u.x]"),
+            };
+            self.fmt_return_palette(&index, f)?;
+            return f.write_str("}\n\n");
+        }
         if !same_size || inline_none || !self.is_compressible() {
-            match reverse_rows {
-                true => f.write_fmt(format_args!("    int idx = u.y * {width} + u.x;\n"))?,
-                false => f.write_fmt(format_args!(
-                    "    int idx = ({semi_height} - u.y) * {width} + u.x;\n"
-                ))?,
-            }
+            let idx = self.pixel_index();
+            f.write_fmt(format_args!("{indent}int idx = {idx};\n"))?;
         }
-        if self.is_compressible() {
+        if self.is_run_length() {
+            self.fmt_run_length_decode(intable, f)?;
+        } else if self.is_tight_packing() {
+            self.fmt_tight_unpack(intable, f)?;
+        } else if self.is_compressible() {
             let chunks_in_u32 = match inline_none {
                 true => "CHUNKS_IN_U32".to_string(),
                 false => (32 / bit_shift).to_string(),
             };
             if !same_size || inline_none {
                 f.write_fmt(format_args!(
-                    "    u = ivec2(idx % {chunks_in_u32}, idx / {chunks_in_u32});\n"
+                    "{indent}u = ivec2(idx % {chunks_in_u32}, idx / {chunks_in_u32});\n"
                 ))?;
                 if inline_none {
-                    f.write_str("    int bitShift = 32 / CHUNKS_IN_U32;\n")?;
+                    f.write_fmt(format_args!("{indent}int bitShift = 32 / CHUNKS_IN_U32;\n"))?;
                 }
             }
             let suffix = int_value_suffix(intable);
@@ -521,15 +4300,221 @@ impl<'a> Display<'a> {
                 true => format!("32 / {chunks_in_u32}"),
                 false => bit_shift.to_string(),
             };
-            f.write_fmt(format_args!(
-                "    return PALETTE[BUFFER[{uy}] >> {ux} * {bit_shift} & {rem_coef}];\n",
-            ))?;
+            let chunk = self.buffer_word(&uy);
+            self.fmt_return_palette(&format!("{chunk} >> {ux} * {bit_shift} & {rem_coef}"), f)?;
         } else {
-            f.write_str("    return PALETTE[BUFFER[idx]];\n")?;
+            self.fmt_return_palette("BUFFER[idx]", f)?;
         }
         f.write_str("}\n\n")
     }
+    /// position of the pixel `u` in the buffer before packing
+    fn pixel_index(&self) -> String {
+        let [width, height] = match self.config.inline_level == InlineLevel::None {
+            true => ["WIDTH".to_string(), "HEIGHT".to_string()],
+            false => self.entity.size.map(|x| x.to_string()),
+        };
+        let semi_height = match self.config.inline_level == InlineLevel::None {
+            true => "HEIGHT - 1".to_string(),
+            false => (self.entity.size[1] - 1).to_string(),
+        };
+        let buffer_format = self.config.buffer_format;
+        match (buffer_format.column_major, buffer_format.reverse_rows) {
+            (false, true) => format!("u.y * {width} + u.x"),
+            (false, false) => format!("({semi_height} - u.y) * {width} + u.x"),
+            (true, true) => format!("u.x * {height} + u.y"),
+            (true, false) => format!("u.x * {height} + {semi_height} - u.y"),
+        }
+    }
+    /// alpha levels packed as `BufferFormat::reverse_each_chunk`, and whether they fit `int`
+    fn alpha_buffer(&self) -> Option<(Vec<u32>, bool)> {
+        let alpha = self.entity.alpha.as_ref()?;
+        let levels = self.ordered_layer(&alpha.levels);
+        let reverse_each_chunk = self.config.buffer_format.reverse_each_chunk;
+        let buffer = pack_chunks(&levels, alpha.bits as usize, reverse_each_chunk);
+        let intable = !self.config.buffer_format.force_uint
+            && buffer.iter().copied().max().unwrap_or(0) < 0x80000000;
+        Some((buffer, intable))
+    }
+    /// Writes `ALPHA` and `getAlpha`, which returns the alpha of the pixel `u` in `0.0..=1.0`.
+    fn fmt_get_alpha(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (Some(alpha), Some((buffer, intable))) = (&self.entity.alpha, self.alpha_buffer())
+        else {
+            return Ok(());
+        };
+        let indent = self.indent();
+        let bits = alpha.bits as usize;
+        let inline_none = self.config.inline_level == InlineLevel::None;
+        if inline_none {
+            f.write_fmt(format_args!("const int ALPHA_BITS = {bits};\n"))?;
+        }
+        let int_type = int_type(intable);
+        f.write_fmt(format_args!("const {int_type} ALPHA[] = "))?;
+        self.fmt_int_array(&buffer, intable, int_type, 1, 8, f)?;
+        let suffix = int_value_suffix(intable);
+        let (chunks_in_u32, rem_coef, max, bits) = match inline_none {
+            true => (
+                "(32 / ALPHA_BITS)".to_string(),
+                format!("(1{suffix} << ALPHA_BITS) - 1{suffix}"),
+                "float((1 << ALPHA_BITS) - 1)".to_string(),
+                "ALPHA_BITS".to_string(),
+            ),
+            false => (
+                (32 / bits).to_string(),
+                format!("{}{suffix}", (1 << bits) - 1),
+                format!("{}.0", (1 << bits) - 1),
+                bits.to_string(),
+            ),
+        };
+        let offset = match self.config.buffer_format.reverse_each_chunk {
+            true => format!("idx % {chunks_in_u32}"),
+            false => format!("({chunks_in_u32} - 1 - idx % {chunks_in_u32})"),
+        };
+        let idx = self.pixel_index();
+        f.write_fmt(format_args!(
+            "float getAlpha(in ivec2 u) {{
+{indent}int idx = {idx};
+{indent}return float(ALPHA[idx / {chunks_in_u32}] >> {offset} * {bits} & {rem_coef}) / {max};
+}}\n\n"
+        ))
+    }
+    /// the guard at the top of `getColor` by `DisplayConfig::bounds_check`
+    fn fmt_bounds_check(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indent = self.indent();
+        let [width, height] = match self.config.inline_level == InlineLevel::None {
+            true => ["WIDTH".to_string(), "HEIGHT".to_string()],
+            false => self.entity.size.map(|x| x.to_string()),
+        };
+        match self.config.bounds_check {
+            BoundsCheck::Unchecked => Ok(()),
+            BoundsCheck::Clamp => f.write_fmt(format_args!(
+                "{indent}u = clamp(u, ivec2(0), ivec2({width}, {height}) - 1);\n"
+            )),
+            BoundsCheck::Background => {
+                let color = match self.config.background {
+                    BackgroundMode::Color(color) => color,
+                    BackgroundMode::Gray => 0x808080,
+                    _ => 0x000000,
+                };
+                let background = self.return_color(&self.color_display(color).to_string());
+                let reset = match self.entity.transparent {
+                    Some(_) => "transparent = false; ",
+                    None => "",
+                };
+                f.write_fmt(format_args!(
+                    "{indent}if (u != abs(u) || u.x >= {width} || u.y >= {height}) {{ {reset}return {background}; }}\n"
+                ))
+            }
+        }
+    }
+    /// Walks the runs until the one containing `idx`.
+    fn fmt_run_length_decode(&self, intable: bool, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indent = self.indent();
+        let int_type = int_type(intable);
+        let suffix = int_value_suffix(intable);
+        f.write_fmt(format_args!(
+            "{indent}int end = 0, n = -1;
+{indent}{int_type} run;
+{indent}do {{
+{indent}{indent}run = BUFFER[++n];
+{indent}{indent}end += int(run >> 16);
+{indent}}} while (end <= idx);\n"
+        ))?;
+        self.fmt_return_palette(&format!("run & 0xffff{suffix}"), f)
+    }
+    /// Reads `bit_width` bits from `idx * bit_width` of the bit stream, spanning up to two words.
+    fn fmt_tight_unpack(&self, intable: bool, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indent = self.indent();
+        let bit_width = self.bit_shift();
+        let int_type = int_type(intable);
+        let suffix = int_value_suffix(intable);
+        let (width_name, rem_coef) = match self.config.inline_level == InlineLevel::None {
+            true => (
+                "BIT_WIDTH".to_string(),
+                format!("(1{suffix} << BIT_WIDTH) - 1{suffix}"),
+            ),
+            false => (
+                bit_width.to_string(),
+                format!("{}{suffix}", (1 << bit_width) - 1),
+            ),
+        };
+        let (word, next_word) = (self.buffer_word("word"), self.buffer_word("word + 1"));
+        f.write_fmt(format_args!(
+            "{indent}int bit = idx * {width_name}, word = bit / 32, offset = bit % 32;
+{indent}{int_type} chunk = {word} >> offset;\n"
+        ))?;
+        if 32 % bit_width != 0 {
+            f.write_fmt(format_args!(
+                "{indent}if (offset + {width_name} > 32) chunk |= {next_word} << (32 - offset);\n"
+            ))?;
+        }
+        self.fmt_return_palette(&format!("chunk & {rem_coef}"), f)
+    }
+    /// `getColor(u)` as `vec4` if it has four components, unpacked from `PaletteFormat::PackedARGB`
+    fn vec4_color(&self) -> Option<&'static str> {
+        match self.config.palette_format {
+            PaletteFormat::PackedARGB if !self.config.vec4_get_color => {
+                Some("unpackARGB(getColor(u))")
+            }
+            format if self.config.vec4_get_color || format.has_alpha() => Some("getColor(u)"),
+            _ => None,
+        }
+    }
+    /// `getColor(u)` converted to `vec3`
+    fn rgb_get_color(&self) -> String {
+        if let Some(color) = self.vec4_color() {
+            return format!("{color}.xyz");
+        }
+        match self.config.palette_format {
+            format if format.is_integer() => "int2rgb(getColor(u))",
+            PaletteFormat::Grayscale => "vec3(getColor(u))",
+            _ => "getColor(u)",
+        }
+        .to_string()
+    }
+    /// `getColor(u)` converted to `vec4`, with the alpha of the palette and `getAlpha(u)` if any
+    fn rgba_get_color(&self) -> String {
+        match (self.vec4_color(), &self.entity.alpha) {
+            (Some(color), None) => color.to_string(),
+            (Some(color), Some(_)) => format!("{color} * vec4(1, 1, 1, getAlpha(u))"),
+            (None, None) => format!("vec4({}, 1)", self.rgb_get_color()),
+            (None, Some(_)) => format!("vec4({}, getAlpha(u))", self.rgb_get_color()),
+        }
+    }
+    /// the alpha of the pixel `u` by the palette and the alpha buffer, `None` if it is always opaque
+    fn get_alpha(&self) -> Option<String> {
+        let palette_alpha = (self.vec4_color())
+            .filter(|_| self.config.palette_format.has_alpha())
+            .map(|color| format!("{color}.w"));
+        match (palette_alpha, &self.entity.alpha) {
+            (None, None) => None,
+            (None, Some(_)) => Some("getAlpha(u)".to_string()),
+            (Some(alpha), None) => Some(alpha),
+            (Some(alpha), Some(_)) => Some(format!("{alpha} * getAlpha(u)")),
+        }
+    }
     fn fmt_main(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let transparent = self.entity.transparent.map(|_| "transparent");
+        self.fmt_main_with(
+            &self.rgb_get_color(),
+            &self.rgba_get_color(),
+            self.get_alpha().as_deref(),
+            transparent,
+            f,
+        )
+    }
+    /// Writes `mainImage` drawing the color `get_color` of the pixel `u`, or `get_rgba` as `vec4`,
+    /// where the pixel is transparent if `transparent` holds.
+    /// If there is `alpha`, which `get_rgba` already includes, the color is blended over the opaque backgrounds
+    /// and `get_rgba` is drawn for the others.
+    fn fmt_main_with(
+        &self,
+        get_color: &str,
+        get_rgba: &str,
+        alpha: Option<&str>,
+        transparent: Option<&str>,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let indent = self.indent();
         let (width, height, float_height, half_vec) =
             match self.config.inline_level == InlineLevel::None {
                 true => (
@@ -549,40 +4534,136 @@ impl<'a> Display<'a> {
                     ),
                 ),
             };
-        let get_color = match self.config.palette_format.is_integer() {
-            true => "int2rgb(getColor(u))",
-            false => "getColor(u)",
+        let scaled = match self.config.scale {
+            DisplayScale::FitHeight => format!("(U - 0.5 * r) / r.y * {float_height}"),
+            DisplayScale::Pixels(n) => format!("(U - 0.5 * r) / {n}.0"),
+            DisplayScale::Percent(percent) => format!(
+                "(U - 0.5 * r) / (r.y * {:?}) * {float_height}",
+                percent as f32 / 100.0
+            ),
+        };
+        let rounding = match self.config.rounding {
+            Rounding::Floor => "floor",
+            Rounding::Round => "round",
         };
         f.write_fmt(format_args!(
             "void mainImage(out vec4 O, in vec2 U) {{
-    vec2 r = iResolution.xy;
-    ivec2 u = ivec2(floor((U - 0.5 * r) / r.y * {float_height} + {half_vec}));
-    O.xyz = u == abs(u) && u.x < {width} && u.y < {height} ? {get_color} : vec3(0.5);
-}}\n"
-        ))
+{indent}vec2 r = iResolution.xy;
+{indent}ivec2 u = ivec2({rounding}({scaled} + {half_vec}));\n"
+        ))?;
+        let inside = format!("u == abs(u) && u.x < {width} && u.y < {height}");
+        let edge_color = match alpha {
+            Some(_) => format!("O = {get_rgba}"),
+            None => format!("O.xyz = {get_color}"),
+        };
+        let blend = |background: &str| match alpha {
+            Some(alpha) => format!("mix({background}, {get_color}, {alpha})"),
+            None => get_color.to_string(),
+        };
+        match self.config.background {
+            BackgroundMode::Gray => f.write_fmt(format_args!(
+                "{indent}O.xyz = {inside} ? {} : vec3(0.5);\n",
+                blend("vec3(0.5)")
+            ))?,
+            BackgroundMode::Color(color) => {
+                let background = ColorDisplay {
+                    format: PaletteFormat::RGBFloat,
+                    space_delim: " ",
+                    uppercase_hex: false,
+                    normalization: Normalization::Divide,
+                    color,
+                };
+                f.write_fmt(format_args!(
+                    "{indent}O.xyz = {inside} ? {} : {background};\n",
+                    blend(&background.to_string())
+                ))?
+            }
+            BackgroundMode::Discard => f.write_fmt(format_args!(
+                "{indent}O = {inside} ? {get_rgba} : vec4(0);\n"
+            ))?,
+            BackgroundMode::DiscardFragment => f.write_fmt(format_args!(
+                "{indent}if (!({inside})) discard;\n{indent}O = {get_rgba};\n"
+            ))?,
+            BackgroundMode::ClampToEdge => f.write_fmt(format_args!(
+                "{indent}u = clamp(u, ivec2(0), ivec2({width}, {height}) - 1);
+{indent}{edge_color};\n"
+            ))?,
+            BackgroundMode::Repeat => f.write_fmt(format_args!(
+                "{indent}u = ivec2(mod(vec2(u), vec2({width}, {height})));
+{indent}{edge_color};\n"
+            ))?,
+        }
+        let keeps_alpha = !matches!(
+            self.config.background,
+            BackgroundMode::Gray | BackgroundMode::Color(_)
+        );
+        if alpha.is_some() && keeps_alpha && self.config.premultiplied_alpha {
+            f.write_fmt(format_args!("{indent}O.xyz *= O.w;\n"))?;
+        }
+        if let Some(transparent) = transparent {
+            let background = match self.config.background {
+                BackgroundMode::Gray => "O.xyz = vec3(0.5)".to_string(),
+                BackgroundMode::Color(color) => format!(
+                    "O.xyz = {}",
+                    ColorDisplay {
+                        format: PaletteFormat::RGBFloat,
+                        space_delim: " ",
+                        uppercase_hex: false,
+                        normalization: Normalization::Divide,
+                        color,
+                    }
+                ),
+                BackgroundMode::DiscardFragment => "discard".to_string(),
+                _ => "O = vec4(0)".to_string(),
+            };
+            f.write_fmt(format_args!("{indent}if ({transparent}) {background};\n"))?;
+        }
+        f.write_str("}\n")
+    }
+    fn fmt_twigl(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.config.twigl_mode {
+            TwiglMode::Geekest => self.fmt_geekest("FC", f),
+            TwiglMode::Geek => {
+                f.write_str("void main(){")?;
+                self.fmt_geekest("gl_FragCoord", f)?;
+                f.write_str("o.w=1.;}")
+            }
+            TwiglMode::Classic => {
+                f.write_str(TWIGL_CLASSIC_HEADER)?;
+                self.fmt_geekest("gl_FragCoord", f)?;
+                f.write_str("outColor=o;}")
+            }
+        }
     }
-    fn fmt_geekest(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt_geekest(&self, frag_coord: &str, f: &mut Formatter<'_>) -> std::fmt::Result {
         let [width, height] = self.entity.size;
-        let size_vec = match width == height {
-            true => format!("{}.", width),
-            false => format!("vec2({},{})", width, height),
+        // the same coordinates as `fmt_main`, fitting the height and keeping the aspect ratio
+        let half = |x: u32| match x % 2 {
+            0 => format!("{}.", x / 2),
+            _ => format!("{}.5", x / 2),
+        };
+        let half_vec = match width == height {
+            true => half(width),
+            false => format!("vec2({},{})", half(width), half(height)),
         };
-        f.write_fmt(format_args!("ivec2 u=ivec2(FC.xy/r*{size_vec});"))?;
-        let bit_shift = self.entity.necessary_bit_shift();
+        f.write_fmt(format_args!(
+            "ivec2 u=ivec2(floor(({frag_coord}.xy-.5*r)/r.y*{height}.+{half_vec}));"
+        ))?;
+        let bit_shift = self.bit_shift();
         let chunks_in_u32 = 32 / bit_shift;
         let rem_coef = (1 << bit_shift) - 1;
         let reverse_rows = match self.config.buffer_format.reverse_rows {
             true => String::new(),
             false => format!("{semi_height}-", semi_height = height - 1),
         };
-        if self.is_compressible() && width != chunks_in_u32 as u32 {
+        if !self.is_compressible() || width != chunks_in_u32 as u32 {
             let uy = match self.config.buffer_format.reverse_rows {
                 true => "u.y".to_string(),
                 false => format!("({reverse_rows}u.y)"),
             };
             f.write_fmt(format_args!("int i={uy}*{width}+u.x;"))?;
         }
-        f.write_str("o .xyz=")?;
+        f.write_fmt(format_args!("o .xyz=u==abs(u)&&u.x<{width}&&u.y<{height}?"))?;
         self.fmt_palette_array(f)?;
         f.write_str("[")?;
         let (buffer, intable) = self.compressed_buffer();
@@ -608,8 +4689,10 @@ impl<'a> Display<'a> {
             f.write_fmt(format_args!(
                 "[{in_brace}]>>{shift_size}*{bit_shift}&{rem_coef}{suffix}"
             ))?;
+        } else {
+            f.write_str("[i]")?;
         }
-        f.write_str("];")?;
+        f.write_str("]:vec3(.5);")?;
         Ok(())
     }
 }
@@ -618,18 +4701,328 @@ const INT_TO_RGB: &str = "vec3 int2rgb(int color) {
     return vec3((color & 0xff0000) >> 16, (color & 0xff00) >> 8, color & 0xff) / 255.0;
 }\n\n";
 
+const UNPACK_ARGB: &str = "vec4 unpackARGB(uint color) {
+    return vec4(color >> 16 & 0xffU, color >> 8 & 0xffU, color & 0xffU, color >> 24) / 255.0;
+}\n\n";
+
+const TWIGL_CLASSIC_HEADER: &str = "#version 300 es
+precision highp float;
+uniform vec2 resolution;
+out vec4 outColor;
+void main(){vec2 r=resolution;vec4 o=vec4(0,0,0,1);";
+
+const STANDALONE_HEADER: &str = "uniform vec2 iResolution;
+out vec4 outColor;
+void mainImage(out vec4, in vec2);
+void main() {
+    vec4 color;
+    mainImage(color, gl_FragCoord.xy);
+    outColor = vec4(color.xyz, 1);
+}
+
+";
+
+impl<'a> Display<'a> {
+    fn fmt_header_comment(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [width, height] = self.entity.size;
+        let palette_len = self.entity.palette.len();
+        f.write_fmt(format_args!(
+            "// {width}x{height} pixel art (WIDTH x HEIGHT) with {palette_len} colors.\n"
+        ))?;
+        match (self.is_palette_inlined(), self.config.palette_as_defines) {
+            (true, _) => f.write_str("// The colors are written in getColor.\n")?,
+            (false, true) => f.write_str("// The colors are the macros C0, C1, ...\n")?,
+            (false, false) => f.write_str("// PALETTE is the list of the colors.\n")?,
+        }
+        let CompressionStats {
+            intable, bit_shift, ..
+        } = self.stats();
+        let int_type = int_type(intable);
+        match bit_shift {
+            _ if self.is_run_length() => f.write_fmt(format_args!(
+                "// Each {int_type} of BUFFER is a run of the same palette index, count << 16 | index.\n"
+            ))?,
+            Some(bit_shift) if self.is_tight_packing() => f.write_fmt(format_args!(
+                "// BUFFER is a stream of {bit_shift}-bit palette indices, \
+                 from the least significant bit of each {int_type}.\n"
+            ))?,
+            Some(bit_shift) => {
+                let order = match self.config.buffer_format.reverse_each_chunk {
+                    true => "least",
+                    false => "most",
+                };
+                f.write_fmt(format_args!(
+                    "// BUFFER packs {} palette indices of {bit_shift} bits into each {int_type}, \
+                     from the {order} significant bits.\n",
+                    32 / bit_shift
+                ))?;
+                match self.vector_len() {
+                    2 => f.write_str("// Each uvec2 of BUFFER holds two of the uints.\n")?,
+                    4 => f.write_str("// Each uvec4 of BUFFER holds four of the uints.\n")?,
+                    _ => {}
+                }
+            }
+            None => f.write_str("// BUFFER holds the palette index of each pixel.\n")?,
+        }
+        if let Some(alpha) = &self.entity.alpha {
+            f.write_fmt(format_args!(
+                "// ALPHA packs the alpha of each pixel in {} bits, in the same order as BUFFER.\n",
+                alpha.bits
+            ))?;
+        }
+        match (
+            self.config.buffer_format.column_major,
+            self.config.buffer_format.reverse_rows,
+        ) {
+            (false, true) => f.write_str("// The pixels are stored from the bottom row.\n\n"),
+            (false, false) => f.write_str("// The pixels are stored from the top row.\n\n"),
+            (true, true) => f.write_str(
+                "// The pixels are stored from the left column, each from the bottom.\n\n",
+            ),
+            (true, false) => {
+                f.write_str("// The pixels are stored from the left column, each from the top.\n\n")
+            }
+        }
+    }
+    fn fmt_shadertoy(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let glsl_version = match self.config.template {
+            Template::Standalone => Some(self.config.glsl_version.unwrap_or_default()),
+            Template::Shadertoy => self.config.glsl_version,
+        };
+        if let Some(glsl_version) = glsl_version {
+            f.write_fmt(format_args!(
+                "#version {glsl_version}\nprecision {} float;\n",
+                self.config.precision.name()
+            ))?;
+            if self.config.template == Template::Shadertoy {
+                f.write_str("\n")?;
+            }
+        }
+        if self.config.template == Template::Standalone {
+            let header = match self.config.background {
+                // keeps the alpha of `mainImage`
+                BackgroundMode::Discard => STANDALONE_HEADER.replace("vec4(color.xyz, 1)", "color"),
+                _ => STANDALONE_HEADER.to_string(),
+            };
+            f.write_str(&header.replace("    ", self.indent()))?;
+        }
+        self.fmt_color_code(f)?;
+        self.fmt_main(f)
+    }
+    /// Writes the arrays and `getColor`, i.e. everything but `mainImage`.
+    fn fmt_color_code(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.config.header_comment && self.config.inline_level == InlineLevel::None {
+            self.fmt_header_comment(f)?;
+        }
+        self.fmt_non_inline_palette(f)?;
+        let intable = self.fmt_non_inline_buffer(f)?;
+        if self.config.palette_format.is_integer() {
+            f.write_str(&INT_TO_RGB.replace("    ", self.indent()))?;
+        }
+        if self.config.palette_format == PaletteFormat::PackedARGB {
+            f.write_str(&UNPACK_ARGB.replace("    ", self.indent()))?;
+        }
+        self.fmt_get_color(intable, f)?;
+        self.fmt_get_alpha(f)
+    }
+}
+
+/// non-minified code for Shadertoy, the input of `minify`
+struct ShadertoyCode<'b, 'a>(&'b Display<'a>);
+
+impl<'b, 'a> std::fmt::Display for ShadertoyCode<'b, 'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt_shadertoy(f)
+    }
+}
+
 impl<'a> std::fmt::Display for Display<'a> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.config.inline_level == InlineLevel::Geekest {
-            self.fmt_geekest(f)
-        } else {
-            self.fmt_non_inline_palette(f)?;
-            let intable = self.fmt_non_inline_buffer(f)?;
-            if self.config.palette_format.is_integer() {
-                f.write_str(INT_TO_RGB)?;
+        match self.config.target {
+            Target::Rust => self.fmt_rust(f),
+            Target::C => self.fmt_c(f),
+            Target::Glsl if self.config.inline_level == InlineLevel::Geekest => self.fmt_twigl(f),
+            Target::Glsl if self.config.minify => {
+                f.write_str(&minify::minify(&ShadertoyCode(self).to_string()))
             }
-            self.fmt_get_color(intable, f)?;
-            self.fmt_main(f)
+            Target::Glsl => self.fmt_shadertoy(f),
         }
     }
 }
+
+#[test]
+fn vec4_get_color() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x0000ff],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        vec4_get_color: true,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("const vec3 PALETTE[]"));
+    assert!(string.contains("vec4 getColor(in ivec2 u) {"));
+    assert!(string.contains("return vec4(PALETTE[BUFFER[u.y]"));
+    assert!(string.contains("? getColor(u).xyz : vec3(0.5);"));
+    config.palette_format = PaletteFormat::IntegerHexadecimal;
+    config.background = BackgroundMode::Discard;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("return vec4(int2rgb(PALETTE["));
+    assert!(string.contains("? getColor(u) : vec4(0);"));
+    config.inline_palette = true;
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("return vec4(int2rgb(i == 0 ? 0xff0000 : 0xff), 1.0);"));
+    config.inline_level = InlineLevel::Geekest;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn inline_palette() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        palette_format: PaletteFormat::IntegerHexadecimal,
+        inline_palette: true,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(!string.contains("PALETTE"));
+    assert!(string.contains("return i == 0 ? 0xff0000 : i == 1 ? 0xff00 : 0xff;\n"));
+    let large = PixelArt {
+        palette: (0..5).collect(),
+        buffer: vec![0, 1, 2, 3, 4, 0],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let string = large.display(config).unwrap().to_string();
+    assert!(string.contains("const int PALETTE[] = "));
+    config.inline_level = InlineLevel::Geekest;
+    config.palette_format = PaletteFormat::RGBFloat;
+    assert!(matches!(
+        pixel_art.display(config),
+        Err(Error::NotMeetGeekest)
+    ));
+}
+
+#[test]
+fn elements_per_line() {
+    let pixel_art = PixelArt {
+        palette: vec![0, 1],
+        buffer: (0..40).map(|i| i % 2).collect(),
+        size: [40, 1],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        elements_per_line: Some(16),
+        buffer_format: BufferFormat {
+            force_to_raw: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    let row = "0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1";
+    assert!(string.contains(&format!(
+        "int[](\n    {row},\n    {row},\n    0, 1, 0, 1, 0, 1, 0, 1\n);"
+    )));
+    config.elements_per_line = Some(40);
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains(&format!(
+        "int[](\n    {row}, {row}, 0, 1, 0, 1, 0, 1, 0, 1\n);"
+    )));
+}
+
+#[test]
+fn indent() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        indent: Indent::Tab,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("vec3[](\n\tvec3(255, 0, 0) / 255.0,\n"));
+    assert!(string.contains("\n\tint idx = u.y * WIDTH + u.x;\n"));
+    assert!(string.lines().all(|line| !line.starts_with(' ')));
+    config.indent = Indent::Spaces(2);
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.contains("\n  int idx = u.y * WIDTH + u.x;\n"));
+    assert!(!string.contains("\n   "));
+}
+
+#[test]
+fn header_comment() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: (0..120).map(|i| i % 3).collect(),
+        size: [12, 10],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        header_comment: true,
+        ..Default::default()
+    };
+    let string = pixel_art.display(config).unwrap().to_string();
+    assert!(string.starts_with("// 12x10 pixel art (WIDTH x HEIGHT) with 3 colors.\n"));
+    assert!(string.contains(
+        "// BUFFER packs 16 palette indices of 2 bits into each uint, from the least significant bits.\n"
+    ));
+    config.minify = true;
+    assert!(!pixel_art
+        .display(config)
+        .unwrap()
+        .to_string()
+        .contains("//"));
+    config.minify = false;
+    config.inline_level = InlineLevel::InlineVariable;
+    assert!(!pixel_art
+        .display(config)
+        .unwrap()
+        .to_string()
+        .contains("//"));
+}
+
+#[test]
+fn minify() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: (0..120).map(|i| i % 3).collect(),
+        size: [12, 10],
+        transparent: None,
+        alpha: None,
+    };
+    let mut config = DisplayConfig {
+        palette_as_defines: true,
+        template: Template::Standalone,
+        ..Default::default()
+    };
+    let verbose = pixel_art.display(config).unwrap().to_string();
+    config.minify = true;
+    let minified = pixel_art.display(config).unwrap().to_string();
+    assert!(minified.len() < verbose.len());
+    assert!(minified.starts_with("#version 300 es\nprecision highp float;"));
+    assert!(minified.contains("\n#define C0 vec3(255,0,0)/255.0\n"));
+    assert!(minified.contains("const int WIDTH=12,HEIGHT=10,CHUNKS_IN_U32=16;"));
+    assert!(!minified.contains("  "));
+    assert!(minified.lines().all(|line| line.len() <= 100));
+}
@@ -1,28 +1,241 @@
 use dot2shader::*;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::Path;
 
-fn main() {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() < 2 {
-        panic!("usage: dot2shader-cli <input image file> [config json]");
-    }
-    let path = std::path::Path::new(&args[1]);
-    let buffer = std::fs::read(&path).unwrap_or_else(|e| panic!("{}", e));
-    let pixel_art = PixelArt::from_image(&buffer).unwrap_or_else(|e| panic!("{}", e));
-    let arg_file = if args.len() > 2 {
-        std::fs::read_to_string(&args[2])
-            .ok()
-            .and_then(|string| serde_json::from_str::<DisplayConfig>(&string).ok())
-    } else {
-        None
+const USAGE: &str = "usage: dot2shader-cli <input image file> [config json] [options]
+
+The input image is read from stdin if <input image file> is `-`.
+
+options:
+    -o, --output <path>              write the generated code to <path> instead of stdout
+    -q, --quiet                      do not print the confirmation when writing to a file
+    --palette-format <format>        integer-decimal, integer-hexadecimal, rgb-decimal, rgb-hexadecimal,
+                                     rgb-float, grayscale, rgba-decimal, rgba-float, or packed-argb
+    --inline-level <level>           none, inline-variable, or geekest
+    --[no-]reverse-rows              turn the picture upside down
+    --[no-]reverse-each-chunk        invert bytes of each chunk
+    --[no-]force-raw                 force not to compress the buffer
+    --palette-limit <n>              fail if the palette has more than <n> colors, e.g. 16
+    --glsl-version <version>         prepend `#version <version>`: 300 es, 310 es, 320 es, 330, or 430
+    --transparency                   draw the fully transparent pixels as the background
+    --alpha-threshold <n>            draw the pixels with alpha less than <n> as the background,
+                                     implies --transparency
+    --alpha-bits <n>                 keep the alpha apart from the palette in <n> bits: 1, 2, 4, or 8
+    --palette-alpha                  keep the alpha in the palette, e.g. for rgba-float
+    --frame <n>                      convert the frame <n> of an animated GIF, counted from 0
+    --auto                           pick the buffer format with the smallest buffer,
+                                     ignored with the inline level geekest
+
+The options override the config json, which overrides `default.json`.";
+
+/// command line arguments
+#[derive(Clone, Debug, Default)]
+struct Args {
+    input: String,
+    config: Option<String>,
+    output: Option<String>,
+    quiet: bool,
+    palette_format: Option<PaletteFormat>,
+    inline_level: Option<InlineLevel>,
+    reverse_rows: Option<bool>,
+    reverse_each_chunk: Option<bool>,
+    force_to_raw: Option<bool>,
+    palette_limit: Option<usize>,
+    glsl_version: Option<GlslVersion>,
+    transparency: bool,
+    alpha_threshold: Option<u8>,
+    alpha_bits: Option<u8>,
+    palette_alpha: bool,
+    frame: Option<usize>,
+    auto: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut positional = Vec::new();
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    let path = args.next().ok_or(format!("{arg} requires a path"))?;
+                    parsed.output = Some(path);
+                }
+                "-q" | "--quiet" => parsed.quiet = true,
+                "--palette-format" => {
+                    let name = args.next().ok_or(format!("{arg} requires a format"))?;
+                    parsed.palette_format =
+                        Some(name.parse().map_err(|e: dot2shader::Error| e.to_string())?);
+                }
+                "--inline-level" => {
+                    let name = args.next().ok_or(format!("{arg} requires a level"))?;
+                    parsed.inline_level =
+                        Some(name.parse().map_err(|e: dot2shader::Error| e.to_string())?);
+                }
+                "--reverse-rows" => parsed.reverse_rows = Some(true),
+                "--no-reverse-rows" => parsed.reverse_rows = Some(false),
+                "--reverse-each-chunk" => parsed.reverse_each_chunk = Some(true),
+                "--no-reverse-each-chunk" => parsed.reverse_each_chunk = Some(false),
+                "--force-raw" => parsed.force_to_raw = Some(true),
+                "--no-force-raw" => parsed.force_to_raw = Some(false),
+                "--palette-limit" => {
+                    let limit = args.next().ok_or(format!("{arg} requires a number"))?;
+                    let limit = limit
+                        .parse()
+                        .map_err(|_| format!("invalid palette limit: {limit}"))?;
+                    parsed.palette_limit = Some(limit);
+                }
+                "--glsl-version" => {
+                    let name = args.next().ok_or(format!("{arg} requires a version"))?;
+                    parsed.glsl_version =
+                        Some(name.parse().map_err(|e: dot2shader::Error| e.to_string())?);
+                }
+                "--transparency" => parsed.transparency = true,
+                "--palette-alpha" => parsed.palette_alpha = true,
+                "--auto" => parsed.auto = true,
+                "--alpha-threshold" => {
+                    let threshold = args.next().ok_or(format!("{arg} requires a number"))?;
+                    let threshold = threshold
+                        .parse()
+                        .map_err(|_| format!("invalid alpha threshold: {threshold}"))?;
+                    parsed.alpha_threshold = Some(threshold);
+                }
+                "--alpha-bits" => {
+                    let bits = args.next().ok_or(format!("{arg} requires a number"))?;
+                    let bits = bits
+                        .parse()
+                        .map_err(|_| format!("invalid alpha bits: {bits}"))?;
+                    parsed.alpha_bits = Some(bits);
+                }
+                "--frame" => {
+                    let frame = args.next().ok_or(format!("{arg} requires a number"))?;
+                    let frame = frame
+                        .parse()
+                        .map_err(|_| format!("invalid frame: {frame}"))?;
+                    parsed.frame = Some(frame);
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option: {arg}"))
+                }
+                _ => positional.push(arg),
+            }
+        }
+        let mut positional = positional.into_iter();
+        parsed.input = positional.next().ok_or("no input image file")?;
+        parsed.config = positional.next();
+        match positional.next() {
+            Some(arg) => Err(format!("unexpected argument: {arg}")),
+            None => Ok(parsed),
+        }
+    }
+    /// Overwrites the fields of `config` specified by the options.
+    fn override_config(&self, config: &mut DisplayConfig) {
+        if let Some(palette_format) = self.palette_format {
+            config.palette_format = palette_format;
+        }
+        if let Some(inline_level) = self.inline_level {
+            config.inline_level = inline_level;
+        }
+        if let Some(palette_limit) = self.palette_limit {
+            config.palette_limit = Some(palette_limit);
+        }
+        if let Some(glsl_version) = self.glsl_version {
+            config.glsl_version = Some(glsl_version);
+        }
+        let buffer_format = &mut config.buffer_format;
+        if let Some(reverse_rows) = self.reverse_rows {
+            buffer_format.reverse_rows = reverse_rows;
+        }
+        if let Some(reverse_each_chunk) = self.reverse_each_chunk {
+            buffer_format.reverse_each_chunk = reverse_each_chunk;
+        }
+        if let Some(force_to_raw) = self.force_to_raw {
+            buffer_format.force_to_raw = force_to_raw;
+        }
+    }
+}
+
+fn read_config(path: &Path) -> Result<DisplayConfig, Box<dyn Error>> {
+    let string = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read config file {}: {e}", path.display()))?;
+    serde_json::from_str::<DisplayConfig>(&string)
+        .map_err(|e| format!("malformed config file {}: {e}", path.display()).into())
+}
+
+fn read_input(input: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if input != "-" {
+        return std::fs::read(input)
+            .map_err(|e| format!("cannot read input image {input}: {e}").into());
+    }
+    let mut buffer = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut buffer)
+        .map_err(|e| format!("cannot read input image from stdin: {e}"))?;
+    match buffer.is_empty() {
+        true => Err("input image from stdin is empty".into()),
+        false => Ok(buffer),
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let buffer = read_input(&args.input)?;
+    let options = ImageOptions {
+        transparency: args.transparency || args.alpha_threshold.is_some(),
+        alpha_threshold: args.alpha_threshold.unwrap_or(1),
+        alpha_bits: args.alpha_bits,
+        palette_alpha: args.palette_alpha,
     };
-    let default_json = std::fs::read_to_string("default.json")
-        .ok()
-        .and_then(|string| serde_json::from_str::<DisplayConfig>(&string).ok());
-    let config = match (arg_file, default_json) {
-        (Some(got), _) => got,
-        (None, Some(got)) => got,
-        (None, None) => Default::default(),
+    let pixel_art = match args.frame {
+        Some(frame) => PixelArt::from_gif_frame(&buffer, frame, options),
+        None => PixelArt::from_image_with(&buffer, options),
+    }
+    .map_err(|e| format!("cannot load input image {}: {e}", args.input))?;
+    if pixel_art.palette().len() > PALETTE_SIZE_LIMIT {
+        return Err(format!(
+            "Palette size must be no more than {PALETTE_SIZE_LIMIT}. Palette size: {}",
+            pixel_art.palette().len()
+        )
+        .into());
+    }
+    let default_json = Path::new("default.json");
+    let mut config = match (&args.config, default_json.exists()) {
+        (Some(arg_file), _) => read_config(Path::new(arg_file))?,
+        (None, true) => read_config(default_json)?,
+        (None, false) => Default::default(),
     };
-    let display = pixel_art.display(config).unwrap();
-    println!("{display}");
+    args.override_config(&mut config);
+    if args.auto && config.inline_level != InlineLevel::Geekest {
+        config.buffer_format = pixel_art.best_buffer_format(config.buffer_format);
+    }
+    let display = pixel_art.display(config)?;
+    match &args.output {
+        Some(output) => {
+            std::fs::File::create(output)
+                .map(std::io::BufWriter::new)
+                .and_then(|mut writer| {
+                    display.write_to(&mut writer)?;
+                    writer.flush()
+                })
+                .map_err(|e| format!("cannot write output file {output}: {e}"))?;
+            if !args.quiet {
+                println!("wrote the generated code to {output}");
+            }
+        }
+        None => println!("{display}"),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {e}\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(&args) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
 }
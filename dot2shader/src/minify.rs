@@ -0,0 +1,73 @@
+/// Lines of the minified code are joined up to this width.
+const LINE_WIDTH: usize = 100;
+
+#[inline]
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Whether the space between `prev` and `next` is necessary to separate tokens,
+/// e.g. `int x` or `a - -b`.
+#[inline]
+fn is_separator(prev: char, next: char) -> bool {
+    (is_word_char(prev) && is_word_char(next)) || ("+-".contains(prev) && "+-".contains(next))
+}
+
+/// Removes the spaces from one line except the necessary ones.
+fn minify_line(line: &str) -> String {
+    let mut res = String::with_capacity(line.len());
+    let mut pending_space = false;
+    line.trim().chars().for_each(|c| match c {
+        ' ' => pending_space = true,
+        _ => {
+            if let Some(prev) = res.chars().last() {
+                if pending_space && is_separator(prev, c) {
+                    res.push(' ');
+                }
+            }
+            pending_space = false;
+            res.push(c);
+        }
+    });
+    res
+}
+
+/// Strips the comments and the redundant whitespaces from the generated code and joins the lines up to `LINE_WIDTH`.
+/// Preprocessor directives are kept on their own lines, so the result still compiles.
+pub(crate) fn minify(code: &str) -> String {
+    let mut res = String::with_capacity(code.len());
+    let mut line_len = 0;
+    code.lines()
+        .map(minify_line)
+        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+        .for_each(|line| {
+            let directive = line.starts_with('#');
+            if line_len > 0 && (directive || line_len + line.len() > LINE_WIDTH) {
+                res.push('\n');
+                line_len = 0;
+            }
+            res.push_str(&line);
+            line_len += line.len();
+            if directive {
+                res.push('\n');
+                line_len = 0;
+            }
+        });
+    if line_len > 0 {
+        res.push('\n');
+    }
+    res
+}
+
+#[test]
+fn strip_spaces() {
+    assert_eq!(
+        minify_line("    const int WIDTH = 10, HEIGHT = 10;"),
+        "const int WIDTH=10,HEIGHT=10;"
+    );
+    assert_eq!(
+        minify_line("vec3(176, 242, 99) / 255.0,"),
+        "vec3(176,242,99)/255.0,"
+    );
+    assert_eq!(minify_line("x = a - -b;"), "x=a- -b;");
+}
@@ -0,0 +1,133 @@
+use crate::*;
+
+/// code of the two tabs of Shadertoy by `Display::multipass`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultipassCode {
+    /// code of the Buffer A tab, which draws the pixel art at the bottom left of the buffer
+    /// with alpha `0` at the transparent pixels and the alpha of the alpha buffer at the others.
+    pub buffer_a: String,
+    /// code of the Image tab, which reads the pixels from Buffer A by `texelFetch`.
+    /// `iChannel0` of the Image tab must be Buffer A.
+    pub image: String,
+}
+
+/// one of the tabs of `MultipassCode`
+struct MultipassTab<'b, 'a> {
+    display: &'b Display<'a>,
+    buffer_a: bool,
+}
+
+impl<'b, 'a> std::fmt::Display for MultipassTab<'b, 'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.buffer_a {
+            true => self.display.fmt_buffer_a(f),
+            false => self.display.fmt_image_tab(f),
+        }
+    }
+}
+
+impl<'a> Display<'a> {
+    /// Splits the code for Shadertoy into Buffer A decoding the pixels and Image drawing them,
+    /// so that the arrays are decoded only in Buffer A. Buffer A has the resolution of the screen,
+    /// so it must be at least as large as the pixel art.
+    /// `template`, `glsl_version` and `target` are ignored, and `InlineLevel::Geekest` is the same as
+    /// `InlineLevel::InlineVariable`.
+    pub fn multipass(&self) -> MultipassCode {
+        let mut display = *self;
+        display.config.target = Target::Glsl;
+        display.config.template = Template::Shadertoy;
+        display.config.glsl_version = None;
+        if display.config.inline_level == InlineLevel::Geekest {
+            display.config.inline_level = InlineLevel::InlineVariable;
+        }
+        let code = |buffer_a| {
+            let code = MultipassTab {
+                display: &display,
+                buffer_a,
+            }
+            .to_string();
+            match display.config.minify {
+                true => minify::minify(&code),
+                false => code,
+            }
+        };
+        MultipassCode {
+            buffer_a: code(true),
+            image: code(false),
+        }
+    }
+    fn fmt_buffer_a(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_color_code(f)?;
+        let indent = self.indent();
+        let (width, height) = match self.config.inline_level == InlineLevel::None {
+            true => ("WIDTH".to_string(), "HEIGHT".to_string()),
+            false => (
+                self.entity.size[0].to_string(),
+                self.entity.size[1].to_string(),
+            ),
+        };
+        let get_rgba = self.rgba_get_color();
+        f.write_fmt(format_args!(
+            "void mainImage(out vec4 O, in vec2 U) {{
+{indent}ivec2 u = ivec2(U);
+{indent}O = u.x < {width} && u.y < {height} ? {get_rgba} : vec4(0);\n"
+        ))?;
+        if self.entity.transparent.is_some() {
+            f.write_fmt(format_args!("{indent}if (transparent) O.w = 0.0;\n"))?;
+        }
+        f.write_str("}\n")
+    }
+    fn fmt_image_tab(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.config.inline_level == InlineLevel::None {
+            let [width, height] = self.entity.size;
+            f.write_fmt(format_args!(
+                "const int WIDTH = {width}, HEIGHT = {height};\n\n"
+            ))?;
+        }
+        let transparent = self
+            .entity
+            .transparent
+            .map(|_| "texelFetch(iChannel0, u, 0).w == 0.0");
+        let texel = "texelFetch(iChannel0, u, 0).xyz";
+        let (get_rgba, alpha) = match self.get_alpha() {
+            Some(_) => (
+                "texelFetch(iChannel0, u, 0)".to_string(),
+                Some("texelFetch(iChannel0, u, 0).w"),
+            ),
+            None => (format!("vec4({texel}, 1)"), None),
+        };
+        self.fmt_main_with(texel, &get_rgba, alpha, transparent, f)
+    }
+}
+
+#[test]
+fn multipass() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x0000ff],
+        buffer: vec![0, 1, 1, 0],
+        size: [2, 2],
+        transparent: Some(1),
+        alpha: None,
+    };
+    let display = pixel_art.display(Default::default()).unwrap();
+    let MultipassCode { buffer_a, image } = display.multipass();
+    assert!(buffer_a.contains("const int WIDTH = 2, HEIGHT = 2"));
+    assert!(buffer_a.contains(
+        "    O = u.x < WIDTH && u.y < HEIGHT ? vec4(getColor(u), 1) : vec4(0);
+    if (transparent) O.w = 0.0;
+}
+"
+    ));
+    assert_eq!(
+        image,
+        "const int WIDTH = 2, HEIGHT = 2;
+
+void mainImage(out vec4 O, in vec2 U) {
+    vec2 r = iResolution.xy;
+    ivec2 u = ivec2(floor((U - 0.5 * r) / r.y * float(HEIGHT) + vec2(WIDTH, HEIGHT) / 2.0));
+    O.xyz = u == abs(u) && u.x < WIDTH && u.y < HEIGHT ? texelFetch(iChannel0, u, 0).xyz : vec3(0.5);
+    if (texelFetch(iChannel0, u, 0).w == 0.0) O.xyz = vec3(0.5);
+}
+"
+    );
+}
@@ -0,0 +1,406 @@
+use crate::PixelArt;
+use std::cmp::Reverse;
+
+#[inline]
+fn channels(color: u32) -> [u8; 3] {
+    let [_, r, g, b] = color.to_be_bytes();
+    [r, g, b]
+}
+
+/// Returns the channel with the largest range in the box and the range.
+fn widest_channel(palette: &[u32], indices: &[usize]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = indices.iter().map(|i| channels(palette[*i])[channel]);
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(channel, range)| (*range, Reverse(*channel)))
+        .unwrap()
+}
+
+/// Average color of the box, weighted by the number of pixels.
+fn average_color(palette: &[u32], histogram: &[usize], indices: &[usize]) -> u32 {
+    let total: u64 = indices.iter().map(|i| histogram[*i] as u64).sum();
+    let weight = |i: usize| match total {
+        0 => 1,
+        _ => histogram[i] as u64,
+    };
+    let total = u64::max(total, indices.len() as u64);
+    let mut sum = [0_u64; 3];
+    indices.iter().for_each(|i| {
+        let color = channels(palette[*i]);
+        (0..3).for_each(|c| sum[c] += color[c] as u64 * weight(*i));
+    });
+    let [r, g, b] = sum.map(|s| ((s + total / 2) / total) as u8);
+    u32::from_be_bytes([0, r, g, b])
+}
+
+#[inline]
+fn squared_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]).powi(2)).sum()
+}
+
+#[inline]
+fn rgb_coordinates(color: u32) -> [f32; 3] {
+    channels(color).map(|c| c as f32)
+}
+
+/// CIE L*a*b* of the sRGB color, under D65.
+fn srgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c / 255.0;
+        match c <= 0.04045 {
+            true => c / 12.92,
+            false => ((c + 0.055) / 1.055).powf(2.4),
+        }
+    });
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let [fx, fy, fz] = [x, y, z].map(|t| match t > 216.0 / 24389.0 {
+        true => t.cbrt(),
+        false => (24389.0 / 27.0 * t + 16.0) / 116.0,
+    });
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// color space in which the nearest color is searched
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Euclidean distance of 8 bit RGB. Fast.
+    #[default]
+    Rgb,
+    /// Euclidean distance of CIE L*a*b* (CIE76). Perceptually better, e.g. for skin tones.
+    Lab,
+}
+
+impl ColorSpace {
+    /// coordinates of the RGB color, `0.0..=255.0` for each channel, in this color space
+    #[inline]
+    fn coordinates(&self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorSpace::Rgb => rgb,
+            ColorSpace::Lab => srgb_to_lab(rgb),
+        }
+    }
+}
+
+/// diagnostics of the palette, see `PixelArt::analyze`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaletteAnalysis {
+    /// the number of the palette entries
+    pub colors: usize,
+    /// the number of the pairs of the colors within the threshold, except the transparent entry
+    pub similar_pairs: usize,
+}
+
+/// Index of the point nearest to `color` by Euclidean distance. Ties go to the smaller index.
+fn nearest_index(points: &[[f32; 3]], color: [f32; 3]) -> usize {
+    points
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            squared_distance(**a, color).total_cmp(&squared_distance(**b, color))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+impl PixelArt {
+    /// Reduces the palette to at most `max_colors` colors by median cut in RGB space.
+    /// Does nothing if the palette already fits. The result is deterministic.
+    /// The transparent pixels become opaque in the color of their palette entry.
+    pub fn quantize(&mut self, max_colors: usize) {
+        let max_colors = usize::max(max_colors, 1);
+        if self.palette.len() <= max_colors {
+            return;
+        }
+        let histogram = self.color_histogram();
+        let mut boxes: Vec<Vec<usize>> = vec![(0..self.palette.len()).collect()];
+        while boxes.len() < max_colors {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, indices)| indices.len() > 1)
+                .map(|(i, indices)| (i, widest_channel(&self.palette, indices)))
+                .max_by_key(|(i, (_, range))| (*range, Reverse(*i)));
+            let (i, channel) = match widest {
+                Some((i, (channel, _))) => (i, channel),
+                None => break,
+            };
+            let indices = &mut boxes[i];
+            indices.sort_by_key(|i| (channels(self.palette[*i])[channel], self.palette[*i]));
+            let total: usize = indices.iter().map(|i| histogram[*i]).sum();
+            let mut acc = 0;
+            let median = indices
+                .iter()
+                .position(|i| {
+                    acc += histogram[*i];
+                    acc * 2 >= total
+                })
+                .unwrap_or(0);
+            let upper = indices.split_off(usize::clamp(median + 1, 1, indices.len() - 1));
+            boxes.push(upper);
+        }
+        self.transparent = None;
+        let mut old2new = vec![0; self.palette.len()];
+        boxes.iter().enumerate().for_each(|(new, indices)| {
+            indices.iter().for_each(|old| old2new[*old] = new as u32);
+        });
+        self.palette = boxes
+            .iter()
+            .map(|indices| average_color(&self.palette, &histogram, indices))
+            .collect();
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+    }
+}
+
+impl PixelArt {
+    /// Counts the pairs of the palette colors within `threshold` by Euclidean distance in RGB space
+    /// without modifying anything, e.g. to suggest `merge_similar_colors` for slightly noisy art.
+    pub fn analyze(&self, threshold: f32) -> PaletteAnalysis {
+        let colors: Vec<[f32; 3]> = (self.palette.iter().enumerate())
+            .filter(|(i, _)| Some(*i as u32) != self.transparent)
+            .map(|(_, color)| channels(*color).map(|c| c as f32))
+            .collect();
+        let similar_pairs = (colors.iter().enumerate())
+            .flat_map(|(i, a)| colors[i + 1..].iter().map(move |b| (a, b)))
+            .filter(|(a, b)| squared_distance(**a, **b) <= threshold * threshold)
+            .count();
+        PaletteAnalysis {
+            colors: self.palette.len(),
+            similar_pairs,
+        }
+    }
+
+    /// Merges each color into a more used one within `threshold` by Euclidean distance in RGB space,
+    /// e.g. the antialiased edges. Returns the number of the merged colors.
+    /// The transparent entry is neither merged nor merged into.
+    /// The remaining colors keep their order and values. The result is deterministic.
+    pub fn merge_similar_colors(&mut self, threshold: f32) -> usize {
+        let histogram = self.color_histogram();
+        let colors: Vec<[f32; 3]> = self
+            .palette
+            .iter()
+            .map(|color| channels(*color).map(|c| c as f32))
+            .collect();
+        let mut order: Vec<usize> = (0..self.palette.len()).collect();
+        order.sort_by_key(|i| Reverse(histogram[*i]));
+        let mut representatives: Vec<usize> = Vec::new();
+        let mut merged_into: Vec<usize> = (0..self.palette.len()).collect();
+        let transparent = self.transparent.map(|idx| idx as usize);
+        order.into_iter().for_each(|i| {
+            let similar = representatives
+                .iter()
+                .filter(|r| Some(**r) != transparent)
+                .find(|r| {
+                    Some(i) != transparent
+                        && squared_distance(colors[**r], colors[i]) <= threshold * threshold
+                });
+            match similar {
+                Some(r) => merged_into[i] = *r,
+                None => representatives.push(i),
+            }
+        });
+        representatives.sort_unstable();
+        let mut old2new = vec![0; self.palette.len()];
+        representatives
+            .iter()
+            .enumerate()
+            .for_each(|(new, old)| old2new[*old] = new as u32);
+        let merged = self.palette.len() - representatives.len();
+        self.palette = representatives.iter().map(|i| self.palette[*i]).collect();
+        self.transparent = self.transparent.map(|idx| old2new[idx as usize]);
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[merged_into[*idx as usize]]);
+        merged
+    }
+
+    /// Replaces the palette with `palette`, mapping each current color to the nearest one in RGB space.
+    /// Does nothing if `palette` is empty.
+    #[inline]
+    pub fn apply_palette(&mut self, palette: &[u32]) {
+        self.apply_palette_in(palette, ColorSpace::Rgb)
+    }
+
+    /// Replaces the palette with `palette`, mapping each current color to the nearest one in `color_space`.
+    /// Does nothing if `palette` is empty. The transparent pixels become opaque.
+    pub fn apply_palette_in(&mut self, palette: &[u32], color_space: ColorSpace) {
+        if palette.is_empty() {
+            return;
+        }
+        let coordinates = |color: u32| color_space.coordinates(rgb_coordinates(color));
+        let points: Vec<[f32; 3]> = palette.iter().map(|color| coordinates(*color)).collect();
+        let old2new: Vec<u32> = self
+            .palette
+            .iter()
+            .map(|color| nearest_index(&points, coordinates(*color)) as u32)
+            .collect();
+        self.buffer
+            .iter_mut()
+            .for_each(|idx| *idx = old2new[*idx as usize]);
+        self.palette = palette.to_vec();
+        self.transparent = None;
+    }
+
+    /// Replaces the palette with `palette`, error-diffusing the current colors by Floyd–Steinberg dithering.
+    /// Does nothing if `palette` is empty. The transparent pixels become opaque.
+    pub fn dither_to_palette(&mut self, palette: &[u32]) {
+        if palette.is_empty() {
+            return;
+        }
+        let [width, height] = self.size.map(|x| x as usize);
+        let points: Vec<[f32; 3]> = palette
+            .iter()
+            .map(|color| rgb_coordinates(*color))
+            .collect();
+        let mut colors: Vec<[f32; 3]> = self
+            .buffer
+            .iter()
+            .map(|idx| rgb_coordinates(self.palette[*idx as usize]))
+            .collect();
+        const NEIGHBORS: [(isize, usize, f32); 4] = [
+            (1, 0, 7.0 / 16.0),
+            (-1, 1, 3.0 / 16.0),
+            (0, 1, 5.0 / 16.0),
+            (1, 1, 1.0 / 16.0),
+        ];
+        for y in 0..height {
+            for x in 0..width {
+                let color = colors[y * width + x].map(|c| c.clamp(0.0, 255.0));
+                let idx = nearest_index(&points, color);
+                let error = [0, 1, 2].map(|c| color[c] - points[idx][c]);
+                NEIGHBORS.iter().for_each(|(dx, dy, coef)| {
+                    let (x, y) = (x as isize + dx, y + dy);
+                    if (0..width as isize).contains(&x) && y < height {
+                        let color = &mut colors[y * width + x as usize];
+                        (0..3).for_each(|c| color[c] += error[c] * coef);
+                    }
+                });
+                self.buffer[y * width + x] = idx as u32;
+            }
+        }
+        self.palette = palette.to_vec();
+        self.transparent = None;
+    }
+}
+
+#[test]
+fn quantize() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0xff0000, 0xf00000, 0x0000ff, 0x0000f0],
+        buffer: vec![0, 1, 2, 3, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let mut fitted = pixel_art.clone();
+    fitted.quantize(4);
+    assert_eq!(fitted, pixel_art);
+    let mut again = pixel_art.clone();
+    pixel_art.quantize(2);
+    again.quantize(2);
+    assert_eq!(again, pixel_art);
+    assert_eq!(pixel_art.palette(), &vec![0x0000fa, 0xfa0000]);
+    assert_eq!(pixel_art.buffer(), &vec![1, 1, 0, 0, 1, 0]);
+}
+
+#[test]
+fn merge_similar_colors() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0x000000, 0x101010, 0xffffff, 0xf8f8f8, 0x808080],
+        buffer: vec![1, 0, 1, 2, 3, 4, 2, 2, 1],
+        size: [3, 3],
+        transparent: None,
+        alpha: None,
+    };
+    let mut again = pixel_art.clone();
+    assert_eq!(pixel_art.merge_similar_colors(0.0), 0);
+    assert_eq!(pixel_art, again);
+    assert_eq!(pixel_art.merge_similar_colors(30.0), 2);
+    assert_eq!(again.merge_similar_colors(30.0), 2);
+    assert_eq!(again, pixel_art);
+    // the more used color of each pair remains.
+    assert_eq!(pixel_art.palette(), &vec![0x101010, 0xffffff, 0x808080]);
+    assert_eq!(pixel_art.buffer(), &vec![0, 0, 0, 1, 1, 2, 1, 1, 0]);
+}
+
+#[test]
+fn analyze() {
+    let pixel_art = PixelArt {
+        palette: vec![0x000000, 0x101010, 0xffffff, 0xf8f8f8, 0x0c0c0c],
+        buffer: vec![1, 0, 1, 2, 3, 4],
+        size: [3, 2],
+        transparent: Some(4),
+        alpha: None,
+    };
+    let analysis = pixel_art.analyze(30.0);
+    assert_eq!(analysis.colors, 5);
+    assert_eq!(analysis.similar_pairs, 2);
+    assert_eq!(pixel_art.analyze(0.0).similar_pairs, 0);
+}
+
+#[test]
+fn apply_palette() {
+    let mut pixel_art = PixelArt {
+        palette: vec![0x202020, 0xe0e0e0, 0xff0000],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    pixel_art.apply_palette(&[0xffffff, 0x000000]);
+    assert_eq!(pixel_art.palette(), &vec![0xffffff, 0x000000]);
+    assert_eq!(pixel_art.buffer(), &vec![1, 0, 1, 0, 1, 1]);
+    assert_eq!(pixel_art.dimensions(), [3, 2]);
+}
+
+#[test]
+fn apply_palette_in_lab() {
+    let navy = PixelArt {
+        palette: vec![0x000070],
+        buffer: vec![0],
+        size: [1, 1],
+        transparent: None,
+        alpha: None,
+    };
+    let mut rgb = navy.clone();
+    rgb.apply_palette_in(&[0x0000ff, 0x000000], ColorSpace::Rgb);
+    assert_eq!(rgb.buffer(), &vec![1]);
+    let mut lab = navy;
+    lab.apply_palette_in(&[0x0000ff, 0x000000], ColorSpace::Lab);
+    assert_eq!(lab.buffer(), &vec![0]);
+    let white = srgb_to_lab([255.0; 3]);
+    assert!(white
+        .iter()
+        .zip([100.0, 0.0, 0.0])
+        .all(|(x, y)| (x - y).abs() < 0.05));
+}
+
+#[test]
+fn dither_to_palette() {
+    let gradient: Vec<u32> = (0..16).map(|i| 0x111111 * i).collect();
+    let mut pixel_art = PixelArt {
+        palette: gradient.clone(),
+        buffer: (0..64).map(|i| i % 16).collect(),
+        size: [16, 4],
+        transparent: None,
+        alpha: None,
+    };
+    pixel_art.dither_to_palette(&[0x000000, 0xffffff]);
+    assert_eq!(pixel_art.palette(), &vec![0x000000, 0xffffff]);
+    // the mean brightness of the gradient is kept.
+    let whites = pixel_art.buffer().iter().filter(|idx| **idx == 1).count();
+    assert_eq!(
+        whites,
+        gradient.iter().map(|c| c & 0xff).sum::<u32>() as usize * 4 / 255
+    );
+    // unlike nearest color mapping, some dark pixels get white and some bright pixels get black.
+    assert!(pixel_art.buffer()[..8].contains(&1));
+    assert!(pixel_art.buffer()[56..].contains(&0));
+}
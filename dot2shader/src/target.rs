@@ -0,0 +1,242 @@
+use crate::*;
+
+impl<'a> Display<'a> {
+    /// palette entries as integers, decimal only if `PaletteFormat::IntegerDecimal`
+    fn data_palette(&self) -> Vec<String> {
+        let format = match self.config.palette_format {
+            PaletteFormat::IntegerDecimal => PaletteFormat::IntegerDecimal,
+            _ => PaletteFormat::IntegerHexadecimal,
+        };
+        self.entity
+            .palette
+            .iter()
+            .map(|color| {
+                ColorDisplay {
+                    format,
+                    space_delim: " ",
+                    uppercase_hex: self.config.uppercase_hex,
+                    normalization: Normalization::Divide,
+                    color: *color,
+                }
+                .to_string()
+            })
+            .collect()
+    }
+    /// the name and the value of the constant needed to unpack the buffer, if it is packed
+    fn data_packing(&self) -> Option<(&'static str, usize)> {
+        match (
+            self.is_run_length(),
+            self.is_tight_packing(),
+            self.is_compressible(),
+        ) {
+            (true, _, _) | (_, _, false) => None,
+            (false, true, _) => Some(("BIT_WIDTH", self.bit_shift())),
+            (false, false, true) => Some(("CHUNKS_IN_U32", 32 / self.bit_shift())),
+        }
+    }
+    /// the index base, only if it is not 0
+    fn data_index_base(&self) -> Option<u32> {
+        Some(self.config.index_offset()).filter(|base| *base > 0)
+    }
+    /// the transparent index in the index base
+    fn data_transparent(&self) -> Option<u32> {
+        (self.entity.transparent).map(|idx| idx + self.config.index_offset())
+    }
+    /// Writes `values` with a trailing comma, `per_line` in each line.
+    fn fmt_data_elements(
+        &self,
+        values: &[String],
+        per_line: usize,
+        f: &mut Formatter<'_>,
+    ) -> std::fmt::Result {
+        let indent = self.indent();
+        values
+            .chunks(usize::max(per_line, 1))
+            .try_for_each(|line| f.write_fmt(format_args!("{indent}{},\n", line.join(", "))))
+    }
+    /// the buffer words and the number of them in each line
+    fn data_buffer(&self, suffix: &str) -> (Vec<String>, usize) {
+        let (buffer, _) = self.compressed_buffer();
+        let per_line = match (self.config.elements_per_line, self.is_compressible()) {
+            (Some(elements_per_line), _) => elements_per_line,
+            (None, true) => 8,
+            (None, false) => self.entity.size[0] as usize,
+        };
+        let buffer = buffer
+            .iter()
+            .map(|word| format!("{}{suffix}", self.int_literal(*word)))
+            .collect();
+        (buffer, per_line)
+    }
+    /// `pub const` arrays for `DisplayConfig::target == Target::Rust`
+    pub(crate) fn fmt_rust(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [width, height] = self.entity.size;
+        f.write_fmt(format_args!(
+            "pub const SIZE: [u32; 2] = [{width}, {height}];\n"
+        ))?;
+        if let Some((name, value)) = self.data_packing() {
+            f.write_fmt(format_args!("pub const {name}: u32 = {value};\n"))?;
+        }
+        if let Some(base) = self.data_index_base() {
+            f.write_fmt(format_args!("pub const INDEX_BASE: u32 = {base};\n"))?;
+        }
+        if let Some(transparent) = self.data_transparent() {
+            f.write_fmt(format_args!(
+                "pub const TRANSPARENT: u32 = {transparent};\n"
+            ))?;
+        }
+        let palette = self.data_palette();
+        f.write_fmt(format_args!(
+            "pub const PALETTE: [u32; {}] = [\n",
+            palette.len()
+        ))?;
+        self.fmt_data_elements(&palette, 1, f)?;
+        let (buffer, per_line) = self.data_buffer("");
+        f.write_fmt(format_args!(
+            "];\npub const BUFFER: [u32; {}] = [\n",
+            buffer.len()
+        ))?;
+        self.fmt_data_elements(&buffer, per_line, f)?;
+        f.write_str("];\n")
+    }
+    /// `static const` arrays for `DisplayConfig::target == Target::C`
+    pub(crate) fn fmt_c(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [width, height] = self.entity.size;
+        f.write_fmt(format_args!(
+            "#include <stdint.h>\n\n#define WIDTH {width}\n#define HEIGHT {height}\n"
+        ))?;
+        if let Some((name, value)) = self.data_packing() {
+            f.write_fmt(format_args!("#define {name} {value}\n"))?;
+        }
+        if let Some(base) = self.data_index_base() {
+            f.write_fmt(format_args!("#define INDEX_BASE {base}\n"))?;
+        }
+        if let Some(transparent) = self.data_transparent() {
+            f.write_fmt(format_args!("#define TRANSPARENT {transparent}\n"))?;
+        }
+        let palette: Vec<String> = self
+            .data_palette()
+            .into_iter()
+            .map(|color| color + "U")
+            .collect();
+        f.write_str("\nstatic const uint32_t palette[] = {\n")?;
+        self.fmt_data_elements(&palette, 1, f)?;
+        let (buffer, per_line) = self.data_buffer("U");
+        f.write_str("};\nstatic const uint32_t buffer[] = {\n")?;
+        self.fmt_data_elements(&buffer, per_line, f)?;
+        f.write_str("};\n")
+    }
+}
+
+#[test]
+fn rust_target() {
+    mod generated {
+        include!("../resources/rust-target.rs");
+    }
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let config = DisplayConfig {
+        target: Target::Rust,
+        ..Default::default()
+    };
+    let display = pixel_art.display(config).unwrap();
+    assert_eq!(
+        display.to_string(),
+        include_str!("../resources/rust-target.rs")
+    );
+    assert_eq!(generated::SIZE, pixel_art.size);
+    assert_eq!(generated::PALETTE[..], pixel_art.palette[..]);
+    assert_eq!(generated::BUFFER[..], display.compressed_buffer().0[..]);
+    assert_eq!(generated::CHUNKS_IN_U32, 16);
+}
+
+#[test]
+fn c_target() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff],
+        buffer: vec![0, 1, 2, 1, 0, 2],
+        size: [3, 2],
+        transparent: None,
+        alpha: None,
+    };
+    let config = DisplayConfig {
+        target: Target::C,
+        buffer_format: BufferFormat {
+            tight_packing: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let display = pixel_art.display(config).unwrap();
+    let (buffer, _) = display.compressed_buffer();
+    assert_eq!(
+        display.to_string(),
+        format!(
+            "#include <stdint.h>
+
+#define WIDTH 3
+#define HEIGHT 2
+#define BIT_WIDTH 2
+
+static const uint32_t palette[] = {{
+    0xff0000U,
+    0xff00U,
+    0xffU,
+}};
+static const uint32_t buffer[] = {{
+    {}U,
+}};
+",
+            buffer[0]
+        )
+    );
+}
+
+#[test]
+fn index_base() {
+    let pixel_art = PixelArt {
+        palette: vec![0xff0000, 0x00ff00, 0x0000ff, 0xffffff],
+        buffer: vec![0, 1, 2, 3, 0, 2],
+        size: [3, 2],
+        transparent: Some(3),
+        alpha: None,
+    };
+    let config = DisplayConfig {
+        target: Target::Rust,
+        index_base: IndexBase::One,
+        buffer_format: BufferFormat {
+            reverse_rows: false,
+            force_to_raw: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let display = pixel_art.display(config).unwrap();
+    assert_eq!(display.compressed_buffer().0, vec![1, 2, 3, 4, 1, 3]);
+    let code = display.to_string();
+    assert!(code.contains("pub const INDEX_BASE: u32 = 1;\npub const TRANSPARENT: u32 = 4;\n"));
+    // the largest index 4 needs 3 bits, packed by 4 bits
+    let config = DisplayConfig {
+        buffer_format: BufferFormat::default(),
+        ..config
+    };
+    let code = pixel_art.display(config).unwrap().to_string();
+    assert!(code.contains("pub const CHUNKS_IN_U32: u32 = 8;\n"));
+    let glsl = DisplayConfig {
+        target: Target::Glsl,
+        ..config
+    };
+    let zero = DisplayConfig {
+        index_base: IndexBase::Zero,
+        ..glsl
+    };
+    assert_eq!(
+        pixel_art.display(glsl).unwrap().to_string(),
+        pixel_art.display(zero).unwrap().to_string()
+    );
+}
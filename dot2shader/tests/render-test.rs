@@ -42,25 +42,19 @@ fn render(display: &glium::Display, pixels: &[u8], config: DisplayConfig) -> (Ve
     .unwrap();
 
     let pixel_art = PixelArt::from_image(pixels).unwrap();
-    let generated = pixel_art.display(config).unwrap().to_string();
     let frag_shader = match config.inline_level == InlineLevel::Geekest {
         false => {
-            "#version 300 es
-precision highp float;
-uniform vec2 iResolution;
-out vec4 outColor;
-void mainImage(out vec4, in vec2);
-void main() {
-    vec4 color;
-    mainImage(color, gl_FragCoord.xy);
-    outColor = vec4(color.xyz, 1);
-}
-"
-            .to_string()
-                + &generated
+            let config = DisplayConfig {
+                template: Template::Standalone,
+                ..config
+            };
+            pixel_art.display(config).unwrap().to_string()
         }
-        true => format!(
-            "#version 300 es
+        true => {
+            let generated = pixel_art.display(config).unwrap().to_string();
+            match config.twigl_mode {
+                TwiglMode::Geekest => format!(
+                    "#version 300 es
 precision highp float;
 uniform vec2 iResolution;
 out vec4 o;
@@ -72,7 +66,18 @@ void main() {{
     {generated}
 }}
 "
-        ),
+                ),
+                TwiglMode::Geek => format!(
+                    "#version 300 es
+precision highp float;
+uniform vec2 r;
+out vec4 o;
+{generated}
+"
+                ),
+                TwiglMode::Classic => generated,
+            }
+        }
     };
     let program = program!(display,
         300 es => {
@@ -92,6 +97,8 @@ void main() {
     let resolution = display.get_framebuffer_dimensions();
     let uniforms = uniform! {
         iResolution: [resolution.0 as f32, resolution.1 as f32],
+        resolution: [resolution.0 as f32, resolution.1 as f32],
+        r: [resolution.0 as f32, resolution.1 as f32],
     };
     let mut target = display.draw();
     target.clear_color(0.0, 0.0, 0.0, 0.0);
@@ -131,49 +138,162 @@ fn non_geekest_configs() -> impl Iterator<Item = DisplayConfig> {
                     reverse_rows: true,
                     reverse_each_chunk: true,
                     force_to_raw: true,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: false,
                     reverse_each_chunk: true,
                     force_to_raw: true,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: true,
                     reverse_each_chunk: false,
                     force_to_raw: true,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: false,
                     reverse_each_chunk: false,
                     force_to_raw: true,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: true,
                     reverse_each_chunk: true,
                     force_to_raw: false,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: false,
                     reverse_each_chunk: true,
                     force_to_raw: false,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: true,
                     reverse_each_chunk: false,
                     force_to_raw: false,
+                    ..Default::default()
                 },
                 BufferFormat {
                     reverse_rows: false,
                     reverse_each_chunk: false,
                     force_to_raw: false,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    reverse_rows: true,
+                    tight_packing: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    reverse_rows: false,
+                    tight_packing: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    force_uint: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    force_to_raw: true,
+                    force_uint: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    pack_uvec2: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    reverse_rows: false,
+                    reverse_each_chunk: false,
+                    pack_uvec2: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    tight_packing: true,
+                    pack_uvec2: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    pack_uvec4: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    reverse_rows: false,
+                    reverse_each_chunk: false,
+                    pack_uvec4: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    tight_packing: true,
+                    pack_uvec4: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    encoding: BufferEncoding::RunLength,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    reverse_rows: false,
+                    encoding: BufferEncoding::RunLength,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    encoding: BufferEncoding::Smallest,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    column_major: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    reverse_rows: false,
+                    column_major: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    force_to_raw: true,
+                    column_major: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    tight_packing: true,
+                    column_major: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    hex_literals: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    force_to_raw: true,
+                    hex_literals: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    pack_uvec2: true,
+                    hex_literals: true,
+                    ..Default::default()
+                },
+                BufferFormat {
+                    encoding: BufferEncoding::RunLength,
+                    hex_literals: true,
+                    ..Default::default()
                 },
             ]
             .iter()
             .copied()
-            .map(move |buffer_format| DisplayConfig {
-                inline_level,
-                palette_format,
-                buffer_format,
+            .flat_map(move |buffer_format| {
+                [false, true].map(|minify| DisplayConfig {
+                    inline_level,
+                    palette_format,
+                    buffer_format,
+                    minify,
+                    header_comment: true,
+                    ..Default::default()
+                })
             })
         })
 }
@@ -184,32 +304,54 @@ fn geekest_configs() -> impl Iterator<Item = DisplayConfig> {
             reverse_rows: true,
             reverse_each_chunk: true,
             force_to_raw: false,
+            ..Default::default()
         },
         BufferFormat {
             reverse_rows: false,
             reverse_each_chunk: true,
             force_to_raw: false,
+            ..Default::default()
         },
         BufferFormat {
             reverse_rows: true,
             reverse_each_chunk: false,
             force_to_raw: false,
+            ..Default::default()
         },
         BufferFormat {
             reverse_rows: false,
             reverse_each_chunk: false,
             force_to_raw: false,
+            ..Default::default()
+        },
+        BufferFormat {
+            force_uint: true,
+            ..Default::default()
         },
     ]
     .iter()
     .copied()
-    .map(move |buffer_format| DisplayConfig {
-        inline_level: InlineLevel::Geekest,
-        palette_format: PaletteFormat::RGBFloat,
-        buffer_format,
+    .flat_map(move |buffer_format| {
+        [TwiglMode::Geekest, TwiglMode::Geek, TwiglMode::Classic].map(|twigl_mode| DisplayConfig {
+            inline_level: InlineLevel::Geekest,
+            palette_format: PaletteFormat::RGBFloat,
+            buffer_format,
+            twigl_mode,
+            ..Default::default()
+        })
     })
 }
 
+/// 256x256 png whose pixels are all different, too many colors to compress the buffer
+fn all_colors_png() -> Vec<u8> {
+    let image = image::RgbaImage::from_fn(256, 256, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgba8(image)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    png.into_inner()
+}
+
 fn one_render_test(
     display: &glium::Display,
     pixels: &[u8],
@@ -241,36 +383,58 @@ fn render_tests() {
         &display,
         include_bytes!("../resources/heart.png"),
         "non-geekest-heart.png",
-        non_geekest_configs(),
+        non_geekest_configs()
+            .chain(non_geekest_configs().map(|config| DisplayConfig {
+                palette_as_defines: true,
+                ..config
+            }))
+            .chain(non_geekest_configs().map(|config| DisplayConfig {
+                inline_palette: true,
+                ..config
+            })),
     );
     one_render_test(
         &display,
         include_bytes!("../resources/steel.png"),
-        "non-geekest-steel.png",
-        non_geekest_configs(),
+        "steel.png",
+        non_geekest_configs().chain(geekest_configs()),
     );
     one_render_test(
         &display,
         include_bytes!("../resources/random.png"),
-        "non-geekest-random.png",
-        non_geekest_configs(),
+        "random.png",
+        non_geekest_configs().chain(geekest_configs()),
     );
     one_render_test(
         &display,
         include_bytes!("../resources/heart.png"),
-        "geekest-heart.png",
-        geekest_configs(),
+        "non-geekest-heart-red.png",
+        non_geekest_configs().map(|config| DisplayConfig {
+            background: BackgroundMode::Color(0xff0000),
+            ..config
+        }),
     );
+    // steel is 16x16, so the edges of the pixels are between the screen pixels with `Rounding::Round`
     one_render_test(
         &display,
         include_bytes!("../resources/steel.png"),
-        "geekest-steel.png",
+        "non-geekest-steel-round.png",
+        non_geekest_configs().map(|config| DisplayConfig {
+            scale: DisplayScale::Pixels(16),
+            rounding: Rounding::Round,
+            ..config
+        }),
+    );
+    one_render_test(
+        &display,
+        include_bytes!("../resources/heart.png"),
+        "geekest-heart.png",
         geekest_configs(),
     );
     one_render_test(
         &display,
-        include_bytes!("../resources/random.png"),
-        "geekest-random.png",
+        &all_colors_png(),
+        "geekest-all-colors.png",
         geekest_configs(),
     );
 }